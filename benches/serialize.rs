@@ -0,0 +1,68 @@
+//! Measures how long it takes to serialize a project with many large sprites.
+//!
+//! Run it with and without the `rayon` feature to compare sequential and
+//! parallel serialization:
+//!
+//! ```sh
+//! cargo bench --bench serialize
+//! cargo bench --bench serialize --features rayon
+//! ```
+
+use sb3_builder::{block, BlockRef, Constant, InsertionPoint, Project, Variable};
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+const SPRITES: usize = 32;
+const SCRIPTS_PER_SPRITE: usize = 2000;
+const RUNS: usize = 10;
+
+fn main() {
+    let names = (0..SPRITES)
+        .map(|i| format!("Sprite{i}"))
+        .collect::<Vec<_>>();
+    let mut project = Project::default();
+    for name in &names {
+        build_sprite(&mut project, name);
+    }
+
+    let mut times = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            project
+                .write_json(io::sink())
+                .unwrap_or_else(|_| unreachable!());
+            start.elapsed()
+        })
+        .collect::<Vec<_>>();
+    times.sort_unstable();
+
+    let parallel = if cfg!(feature = "rayon") {
+        "parallel"
+    } else {
+        "sequential"
+    };
+    println!(
+        "{parallel} serialization of {SPRITES} sprites: median {:?}, best {:?}",
+        times[RUNS / 2],
+        times.first().copied().unwrap_or(Duration::ZERO),
+    );
+}
+
+fn build_sprite<'strings>(project: &mut Project<'strings>, name: &'strings str) {
+    let mut sprite = project.add_sprite(name);
+    let counter = sprite.add_variable(Variable {
+        name: "counter".to_owned(),
+        value: Constant::Number(0.0),
+    });
+    for _ in 0..SCRIPTS_PER_SPRITE {
+        sprite.start_script(block::when_flag_clicked());
+        let sum = sprite.add(counter.into(), 1.0.into());
+        let product = sprite.mul(sum, 2.0.into());
+        let condition = sprite.lt(product, 100.0.into());
+        let _: InsertionPoint = sprite.if_(condition);
+        let joined = sprite.join("counter: ".into(), counter.into());
+        let _: BlockRef = sprite.put(block::say(joined));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{ListRef, Mutation, Operand, Parameter, RealTarget, VariableRef};
-use std::{fmt, io};
+use std::{borrow::Cow, fmt, io};
 
 pub(crate) struct Block {
     pub(crate) opcode: Opcode,
@@ -10,21 +10,22 @@ pub(crate) struct Block {
 impl Block {
     pub fn serialize(
         &self,
-        inputs: &[(&str, Input)],
-        fields: Option<Fields>,
+        inputs: &[(InputName, Input)],
+        fields: Option<&Fields>,
         mutation: Option<Mutation>,
         target: &RealTarget,
+        stage: &RealTarget,
         writer: &mut dyn io::Write,
     ) -> io::Result<()> {
         write!(writer, r#"{{"opcode":"{:?}","parent":"#, self.opcode)?;
         if let Some(parent) = self.parent {
-            write!(writer, "{parent}")
+            write!(writer, "{}", target.block_id(parent))
         } else {
             write!(writer, "null")
         }?;
         write!(writer, r#","next":"#)?;
         if let Some(next) = self.next {
-            write!(writer, "{next}")
+            write!(writer, "{}", target.block_id(next))
         } else {
             write!(writer, "null")
         }?;
@@ -42,14 +43,14 @@ impl Block {
                 if i != 0 {
                     write!(writer, ",")?;
                 }
-                write!(writer, "{name:?}:")?;
-                input.serialize(target, writer)?;
+                write!(writer, "{name}:")?;
+                input.serialize(target, stage, writer)?;
             }
             write!(writer, "}}")?;
         }
         if let Some(fields) = fields {
             write!(writer, r#","fields":"#)?;
-            fields.serialize(target, writer)?;
+            fields.serialize(target, stage, writer)?;
         }
         if let Some(mutation) = mutation {
             write!(writer, r#","mutation":"#)?;
@@ -71,7 +72,7 @@ impl Block {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Hat<'strings> {
     pub(crate) opcode: Opcode,
     pub(crate) fields: Option<Fields<'strings>>,
@@ -91,6 +92,50 @@ impl Stacking<'_> {
             fields: None,
         }
     }
+
+    /// Whether no blocks can run after this one in the same script.
+    pub(crate) const fn ends_script(&self) -> bool {
+        matches!(self.opcode, Opcode::control_forever)
+            || matches!(self.fields, Some(Fields::StopAll | Fields::StopThisScript))
+    }
+}
+
+/// The position of an item in a list.
+pub enum ListIndex<'strings> {
+    /// The item at a position that starts at 1.
+    At(Operand<'strings>),
+    Last,
+    Random,
+}
+
+impl<'strings> ListIndex<'strings> {
+    pub(crate) fn into_input(self) -> Input<'strings> {
+        match self {
+            Self::At(index) => index.0,
+            Self::Last => Input::String("last".into()),
+            Self::Random => Input::String("random".into()),
+        }
+    }
+}
+
+/// The items to remove with [`delete_of_list`].
+pub enum DeleteIndex<'strings> {
+    /// The item at a position that starts at 1.
+    At(Operand<'strings>),
+    Last,
+    Random,
+    All,
+}
+
+impl<'strings> DeleteIndex<'strings> {
+    pub(crate) fn into_input(self) -> Input<'strings> {
+        match self {
+            Self::At(index) => index.0,
+            Self::Last => Input::String("last".into()),
+            Self::Random => Input::String("random".into()),
+            Self::All => Input::String("all".into()),
+        }
+    }
 }
 
 #[must_use]
@@ -102,10 +147,10 @@ pub const fn when_flag_clicked() -> Hat<'static> {
 }
 
 #[must_use]
-pub const fn when_key_pressed(key: &str) -> Hat<'_> {
+pub fn when_key_pressed<'strings>(key: impl Into<Cow<'strings, str>>) -> Hat<'strings> {
     Hat {
         opcode: Opcode::event_whenkeypressed,
-        fields: Some(Fields::KeyOption(key)),
+        fields: Some(Fields::KeyOption(key.into())),
     }
 }
 
@@ -118,10 +163,10 @@ pub const fn when_cloned() -> Hat<'static> {
 }
 
 #[must_use]
-pub const fn when_received(message: &str) -> Hat<'_> {
+pub fn when_received<'strings>(message: impl Into<Cow<'strings, str>>) -> Hat<'strings> {
     Hat {
         opcode: Opcode::event_whenbroadcastreceived,
-        fields: Some(Fields::BroadcastOption(message)),
+        fields: Some(Fields::BroadcastOption(message.into())),
     }
 }
 
@@ -194,11 +239,11 @@ pub fn delete_all_of_list(list: ListRef) -> Stacking {
 #[must_use]
 pub fn delete_of_list<'strings>(
     list: ListRef<'strings>,
-    index: Operand<'strings>,
+    index: DeleteIndex<'strings>,
 ) -> Stacking<'strings> {
     Stacking {
         opcode: Opcode::data_deleteoflist,
-        inputs: Box::new([("INDEX", index.0)]),
+        inputs: Box::new([("INDEX", index.into_input())]),
         fields: Some(Fields::List(list)),
     }
 }
@@ -212,7 +257,7 @@ pub fn erase_all() -> Stacking<'static> {
 pub fn go_to_back_layer() -> Stacking<'static> {
     Stacking {
         opcode: Opcode::looks_gotofrontback,
-        inputs: Box::new([("FRONT_BACK", Input::String("back"))]),
+        inputs: Box::new([("FRONT_BACK", Input::String(Cow::Borrowed("back")))]),
         fields: None,
     }
 }
@@ -221,7 +266,7 @@ pub fn go_to_back_layer() -> Stacking<'static> {
 pub fn go_to_front_layer() -> Stacking<'static> {
     Stacking {
         opcode: Opcode::looks_gotofrontback,
-        inputs: Box::new([("FRONT_BACK", Input::String("front"))]),
+        inputs: Box::new([("FRONT_BACK", Input::String(Cow::Borrowed("front")))]),
         fields: None,
     }
 }
@@ -240,15 +285,33 @@ pub fn hide() -> Stacking<'static> {
     Stacking::new(Opcode::looks_hide)
 }
 
+#[must_use]
+pub fn hide_list(list: ListRef) -> Stacking {
+    Stacking {
+        opcode: Opcode::data_hidelist,
+        inputs: Box::new([]),
+        fields: Some(Fields::List(list)),
+    }
+}
+
+#[must_use]
+pub fn hide_variable(variable: VariableRef) -> Stacking {
+    Stacking {
+        opcode: Opcode::data_hidevariable,
+        inputs: Box::new([]),
+        fields: Some(Fields::Variable(variable)),
+    }
+}
+
 #[must_use]
 pub fn insert_at_list<'strings>(
     list: ListRef<'strings>,
     item: Operand<'strings>,
-    index: Operand<'strings>,
+    index: ListIndex<'strings>,
 ) -> Stacking<'strings> {
     Stacking {
         opcode: Opcode::data_insertatlist,
-        inputs: Box::new([("ITEM", item.0), ("INDEX", index.0)]),
+        inputs: Box::new([("ITEM", item.0), ("INDEX", index.into_input())]),
         fields: Some(Fields::List(list)),
     }
 }
@@ -275,12 +338,12 @@ pub fn pen_up() -> Stacking<'static> {
 #[must_use]
 pub fn replace<'strings>(
     list: ListRef<'strings>,
-    index: Operand<'strings>,
+    index: ListIndex<'strings>,
     item: Operand<'strings>,
 ) -> Stacking<'strings> {
     Stacking {
         opcode: Opcode::data_replaceitemoflist,
-        inputs: Box::new([("INDEX", index.0), ("ITEM", item.0)]),
+        inputs: Box::new([("INDEX", index.into_input()), ("ITEM", item.0)]),
         fields: Some(Fields::List(list)),
     }
 }
@@ -305,7 +368,7 @@ pub fn say_for_seconds<'strings>(
     message: Operand<'strings>,
 ) -> Stacking<'strings> {
     Stacking {
-        opcode: Opcode::looks_say,
+        opcode: Opcode::looks_sayforsecs,
         inputs: Box::new([("SECS", seconds.0), ("MESSAGE", message.0)]),
         fields: None,
     }
@@ -382,6 +445,24 @@ pub fn show() -> Stacking<'static> {
     Stacking::new(Opcode::looks_show)
 }
 
+#[must_use]
+pub fn show_list(list: ListRef) -> Stacking {
+    Stacking {
+        opcode: Opcode::data_showlist,
+        inputs: Box::new([]),
+        fields: Some(Fields::List(list)),
+    }
+}
+
+#[must_use]
+pub fn show_variable(variable: VariableRef) -> Stacking {
+    Stacking {
+        opcode: Opcode::data_showvariable,
+        inputs: Box::new([]),
+        fields: Some(Fields::Variable(variable)),
+    }
+}
+
 #[must_use]
 pub fn stamp() -> Stacking<'static> {
     Stacking::new(Opcode::pen_stamp)
@@ -414,62 +495,108 @@ pub fn wait(seconds: Operand) -> Stacking {
     }
 }
 
+#[derive(Clone)]
 pub(crate) enum Input<'strings> {
     Substack(Id),
+    /// A reporter held by an [`Operand`] along with the number of blocks that
+    /// had been streamed when it was created. It becomes a `Substack` when it
+    /// is used as an input.
+    Reporter(Id, u32),
     EmptySubstack,
     Number(f64),
-    String(&'strings str),
+    String(Cow<'strings, str>),
+    /// Only exists as a result of constant folding since Scratch has no
+    /// boolean literals. It gets replaced by a block when used as an input.
+    Boolean(bool),
     Variable(VariableRef<'strings>),
     List(ListRef<'strings>),
     Prototype(Id),
 }
 
 impl Input<'_> {
-    fn serialize(&self, target: &RealTarget, writer: &mut dyn io::Write) -> io::Result<()> {
+    fn serialize(
+        &self,
+        target: &RealTarget,
+        stage: &RealTarget,
+        writer: &mut dyn io::Write,
+    ) -> io::Result<()> {
         match *self {
-            Self::Substack(uid) => write!(writer, "[2,{uid}]"),
-            Self::EmptySubstack => unreachable!(),
+            Self::Substack(uid) => write!(writer, "[2,{}]", target.block_id(uid)),
+            Self::EmptySubstack | Self::Boolean(_) | Self::Reporter(..) => unreachable!(),
             Self::Number(n) if n == f64::INFINITY => write!(writer, r#"[1,[4,"Infinity"]]"#),
             Self::Number(n) if n == f64::NEG_INFINITY => write!(writer, r#"[1,[4,"-Infinity"]]"#),
             Self::Number(n) if n.is_nan() => write!(writer, r#"[1,[4,"NaN"]]"#),
             Self::Number(n) => write!(writer, r"[1,[4,{n}]]"),
-            Self::String(s) => write!(writer, r"[1,[10,{s:?}]]"),
-            Self::Variable(VariableRef(id)) => {
-                let name = &target.variables[id].name;
-                write!(writer, r#"[2,[12,{name:?},"v{}"]]"#, id.to_u32())
+            Self::String(ref s) => write!(writer, r"[1,[10,{s:?}]]"),
+            Self::Variable(variable) => {
+                let name = &target.owner(stage, variable.target).variables[variable.id].name;
+                write!(writer, "[2,[12,{name:?},{:?}]]", variable.serialized_id())
             }
-            Self::List(ListRef(id)) => {
-                let name = &target.lists[id].name;
-                write!(writer, r#"[2,[13,{name:?},"l{}"]]"#, id.to_u32())
+            Self::List(list) => {
+                let name = &target.owner(stage, list.target).lists[list.id].name;
+                write!(writer, "[2,[13,{name:?},{:?}]]", list.serialized_id())
             }
-            Self::Prototype(uid) => write!(writer, "[1,{uid}]"),
+            Self::Prototype(uid) => write!(writer, "[1,{}]", target.block_id(uid)),
         }
     }
 }
 
+/// The key of an input in its block.
 #[derive(Clone, Copy)]
+pub(crate) enum InputName {
+    Static(&'static str),
+    /// Arguments of custom blocks are keyed by the ID of their parameter.
+    Parameter(tec::Id<Parameter>),
+}
+
+impl From<&'static str> for InputName {
+    fn from(name: &'static str) -> Self {
+        Self::Static(name)
+    }
+}
+
+impl fmt::Display for InputName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Static(name) => write!(f, "{name:?}"),
+            Self::Parameter(parameter) => write!(f, r#""{}""#, parameter.to_u32()),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub(crate) enum Fields<'strings> {
     Variable(VariableRef<'strings>),
     List(ListRef<'strings>),
     Value(tec::Id<Parameter>),
     Operator(&'static str),
-    KeyOption(&'strings str),
-    BroadcastOption(&'strings str),
+    KeyOption(Cow<'strings, str>),
+    BroadcastOption(Cow<'strings, str>),
     StopAll,
     StopThisScript,
     CloneSelf,
 }
 
 impl Fields<'_> {
-    fn serialize(&self, target: &RealTarget, writer: &mut dyn io::Write) -> io::Result<()> {
+    fn serialize(
+        &self,
+        target: &RealTarget,
+        stage: &RealTarget,
+        writer: &mut dyn io::Write,
+    ) -> io::Result<()> {
         match self {
-            Self::Variable(VariableRef(id)) => {
-                let name = &target.variables[*id].name;
-                write!(writer, r#"{{"VARIABLE":[{name:?},"{}"]}}"#, id.to_u32())
+            Self::Variable(variable) => {
+                let name = &target.owner(stage, variable.target).variables[variable.id].name;
+                let id = variable.serialized_id();
+                write!(writer, r#"{{"VARIABLE":[{name:?},{id:?}]}}"#)
             }
-            Self::List(ListRef(id)) => {
-                let name = &target.lists[*id].name;
-                write!(writer, r#"{{"LIST":[{name:?},"{}"]}}"#, id.to_u32())
+            Self::List(list) => {
+                let name = &target.owner(stage, list.target).lists[list.id].name;
+                write!(
+                    writer,
+                    r#"{{"LIST":[{name:?},{:?}]}}"#,
+                    list.serialized_id()
+                )
             }
             Self::Value(parameter) => {
                 let name = &target.parameters[*parameter].name;
@@ -520,13 +647,18 @@ pub(crate) enum Opcode {
     data_changevariableby,
     data_deletealloflist,
     data_deleteoflist,
+    data_hidelist,
+    data_hidevariable,
     data_insertatlist,
     data_itemnumoflist,
     data_itemoflist,
     data_lengthoflist,
+    data_listcontents,
     data_listcontainsitem,
     data_replaceitemoflist,
     data_setvariableto,
+    data_showlist,
+    data_showvariable,
     event_broadcastandwait,
     event_whenbroadcastreceived,
     event_whenflagclicked,
@@ -534,6 +666,7 @@ pub(crate) enum Opcode {
     looks_gotofrontback,
     looks_hide,
     looks_say,
+    looks_sayforsecs,
     looks_setsizeto,
     looks_show,
     looks_switchcostumeto,
@@ -591,7 +724,7 @@ impl Opcode {
             Self::argument_reporter_string_number => true,
             Self::control_create_clone_of => false,
             Self::control_create_clone_of_menu => true,
-            Self::control_for_each => false,
+            Self::control_for_each => true,
             Self::control_forever => false,
             Self::control_if => false,
             Self::control_if_else => false,
@@ -605,13 +738,18 @@ impl Opcode {
             Self::data_changevariableby => true,
             Self::data_deletealloflist => true,
             Self::data_deleteoflist => true,
+            Self::data_hidelist => true,
+            Self::data_hidevariable => true,
             Self::data_insertatlist => true,
             Self::data_itemnumoflist => true,
             Self::data_itemoflist => true,
             Self::data_lengthoflist => true,
+            Self::data_listcontents => true,
             Self::data_listcontainsitem => true,
             Self::data_replaceitemoflist => true,
             Self::data_setvariableto => true,
+            Self::data_showlist => true,
+            Self::data_showvariable => true,
             Self::event_broadcastandwait => false,
             Self::event_whenbroadcastreceived => true,
             Self::event_whenflagclicked => false,
@@ -619,6 +757,7 @@ impl Opcode {
             Self::looks_gotofrontback => false,
             Self::looks_hide => false,
             Self::looks_say => false,
+            Self::looks_sayforsecs => false,
             Self::looks_setsizeto => false,
             Self::looks_show => false,
             Self::looks_switchcostumeto => false,
@@ -666,88 +805,103 @@ impl Opcode {
         }
     }
 
+    pub(crate) const fn input_count(self) -> Option<usize> {
+        match self.input_names() {
+            Some(names) => Some(names.len()),
+            None => None,
+        }
+    }
+
+    /// The names of the inputs of a block in the order that they are stored
+    /// in, or `None` for custom blocks, whose inputs are their parameters.
     #[expect(
         clippy::match_same_arms,
         reason = "easier to keep opcodes in order when the arms aren't merged"
     )]
-    pub(crate) const fn input_count(self) -> Option<u32> {
+    pub(crate) const fn input_names(self) -> Option<&'static [&'static str]> {
         Some(match self {
-            Self::argument_reporter_boolean => 0,
-            Self::argument_reporter_string_number => 0,
-            Self::control_create_clone_of => 1,
-            Self::control_create_clone_of_menu => 0,
-            Self::control_for_each => 2,
-            Self::control_forever => 1,
-            Self::control_if => 2,
-            Self::control_if_else => 3,
-            Self::control_repeat => 2,
-            Self::control_repeat_until => 2,
-            Self::control_start_as_clone => 0,
-            Self::control_stop => 0,
-            Self::control_wait => 1,
-            Self::control_while => 2,
-            Self::data_addtolist => 1,
-            Self::data_changevariableby => 1,
-            Self::data_deletealloflist => 0,
-            Self::data_deleteoflist => 1,
-            Self::data_insertatlist => 2,
-            Self::data_itemnumoflist => 1,
-            Self::data_itemoflist => 1,
-            Self::data_lengthoflist => 0,
-            Self::data_listcontainsitem => 1,
-            Self::data_replaceitemoflist => 2,
-            Self::data_setvariableto => 1,
-            Self::event_broadcastandwait => 1,
-            Self::event_whenbroadcastreceived => 0,
-            Self::event_whenflagclicked => 0,
-            Self::event_whenkeypressed => 0,
-            Self::looks_gotofrontback => 1,
-            Self::looks_hide => 0,
-            Self::looks_say => 1,
-            Self::looks_setsizeto => 1,
-            Self::looks_show => 1,
-            Self::looks_switchcostumeto => 1,
-            Self::motion_changexby => 1,
-            Self::motion_changeyby => 1,
-            Self::motion_gotoxy => 2,
-            Self::motion_movesteps => 1,
-            Self::motion_setx => 1,
-            Self::motion_sety => 1,
-            Self::motion_xposition => 0,
-            Self::motion_yposition => 0,
-            Self::operator_add => 2,
-            Self::operator_and => 2,
-            Self::operator_contains => 2,
-            Self::operator_divide => 2,
-            Self::operator_equals => 2,
-            Self::operator_gt => 2,
-            Self::operator_join => 2,
-            Self::operator_length => 1,
-            Self::operator_letter_of => 2,
-            Self::operator_lt => 2,
-            Self::operator_mathop => 1,
-            Self::operator_mod => 2,
-            Self::operator_multiply => 2,
-            Self::operator_not => 1,
-            Self::operator_or => 2,
-            Self::operator_random => 2,
-            Self::operator_subtract => 2,
-            Self::pen_clear => 0,
-            Self::pen_penDown => 0,
-            Self::pen_penUp => 0,
-            Self::pen_setPenColorTo => 1,
-            Self::pen_setPenSizeTo => 1,
-            Self::pen_stamp => 0,
+            Self::argument_reporter_boolean => &[],
+            Self::argument_reporter_string_number => &[],
+            Self::control_create_clone_of => &["CLONE_OPTION"],
+            Self::control_create_clone_of_menu => &[],
+            Self::control_for_each => &["SUBSTACK", "VALUE"],
+            Self::control_forever => &["SUBSTACK"],
+            Self::control_if => &["SUBSTACK", "CONDITION"],
+            Self::control_if_else => &["SUBSTACK", "SUBSTACK2", "CONDITION"],
+            Self::control_repeat => &["SUBSTACK", "TIMES"],
+            Self::control_repeat_until => &["SUBSTACK", "CONDITION"],
+            Self::control_start_as_clone => &[],
+            Self::control_stop => &[],
+            Self::control_wait => &["DURATION"],
+            Self::control_while => &["SUBSTACK", "CONDITION"],
+            Self::data_addtolist => &["ITEM"],
+            Self::data_changevariableby => &["VALUE"],
+            Self::data_deletealloflist => &[],
+            Self::data_deleteoflist => &["INDEX"],
+            Self::data_hidelist => &[],
+            Self::data_hidevariable => &[],
+            Self::data_insertatlist => &["ITEM", "INDEX"],
+            Self::data_itemnumoflist => &["ITEM"],
+            Self::data_itemoflist => &["INDEX"],
+            Self::data_lengthoflist => &[],
+            Self::data_listcontents => &[],
+            Self::data_listcontainsitem => &["ITEM"],
+            Self::data_replaceitemoflist => &["INDEX", "ITEM"],
+            Self::data_setvariableto => &["VALUE"],
+            Self::data_showlist => &[],
+            Self::data_showvariable => &[],
+            Self::event_broadcastandwait => &["BROADCAST_INPUT"],
+            Self::event_whenbroadcastreceived => &[],
+            Self::event_whenflagclicked => &[],
+            Self::event_whenkeypressed => &[],
+            Self::looks_gotofrontback => &["FRONT_BACK"],
+            Self::looks_hide => &[],
+            Self::looks_say => &["MESSAGE"],
+            Self::looks_sayforsecs => &["SECS", "MESSAGE"],
+            Self::looks_setsizeto => &["SIZE"],
+            Self::looks_show => &[],
+            Self::looks_switchcostumeto => &["COSTUME"],
+            Self::motion_changexby => &["DX"],
+            Self::motion_changeyby => &["DY"],
+            Self::motion_gotoxy => &["X", "Y"],
+            Self::motion_movesteps => &["STEPS"],
+            Self::motion_setx => &["X"],
+            Self::motion_sety => &["Y"],
+            Self::motion_xposition => &[],
+            Self::motion_yposition => &[],
+            Self::operator_add => &["NUM1", "NUM2"],
+            Self::operator_and => &["OPERAND1", "OPERAND2"],
+            Self::operator_contains => &["STRING1", "STRING2"],
+            Self::operator_divide => &["NUM1", "NUM2"],
+            Self::operator_equals => &["OPERAND1", "OPERAND2"],
+            Self::operator_gt => &["OPERAND1", "OPERAND2"],
+            Self::operator_join => &["STRING1", "STRING2"],
+            Self::operator_length => &["STRING"],
+            Self::operator_letter_of => &["STRING", "LETTER"],
+            Self::operator_lt => &["OPERAND1", "OPERAND2"],
+            Self::operator_mathop => &["NUM"],
+            Self::operator_mod => &["NUM1", "NUM2"],
+            Self::operator_multiply => &["NUM1", "NUM2"],
+            Self::operator_not => &["OPERAND"],
+            Self::operator_or => &["OPERAND1", "OPERAND2"],
+            Self::operator_random => &["FROM", "TO"],
+            Self::operator_subtract => &["NUM1", "NUM2"],
+            Self::pen_clear => &[],
+            Self::pen_penDown => &[],
+            Self::pen_penUp => &[],
+            Self::pen_setPenColorTo => &["COLOR"],
+            Self::pen_setPenSizeTo => &["SIZE"],
+            Self::pen_stamp => &[],
             Self::procedures_call => return None,
-            Self::procedures_definition => 1,
+            Self::procedures_definition => &["custom_block"],
             Self::procedures_prototype => return None,
-            Self::sensing_answer => 0,
-            Self::sensing_askandwait => 1,
-            Self::sensing_keypressed => 1,
-            Self::sensing_mousex => 0,
-            Self::sensing_mousey => 0,
-            Self::sensing_resettimer => 0,
-            Self::sensing_timer => 0,
+            Self::sensing_answer => &[],
+            Self::sensing_askandwait => &["QUESTION"],
+            Self::sensing_keypressed => &["KEY_OPTION"],
+            Self::sensing_mousex => &[],
+            Self::sensing_mousey => &[],
+            Self::sensing_resettimer => &[],
+            Self::sensing_timer => &[],
         })
     }
 }
@@ -0,0 +1,66 @@
+use crate::{Constant, Target, Variable, VariableRef};
+use std::{error::Error, fmt};
+
+/// The most cloud variables that a project can have.
+const MAX_CLOUD_VARIABLES: usize = 10;
+
+/// The prefix that Scratch requires the names of cloud variables to have.
+const CLOUD_PREFIX: &str = "☁ ";
+
+/// Why [`Target::add_cloud_variable`] failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudVariableError {
+    /// Only the stage can have cloud variables.
+    NotStage,
+    /// Cloud variables can only hold finite numbers.
+    NotNumber,
+    /// The project already has as many cloud variables as Scratch allows.
+    TooMany,
+}
+
+impl fmt::Display for CloudVariableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotStage => write!(f, "only the stage can have cloud variables"),
+            Self::NotNumber => write!(f, "cloud variables can only hold finite numbers"),
+            Self::TooMany => write!(
+                f,
+                "a project cannot have more than {MAX_CLOUD_VARIABLES} cloud variables"
+            ),
+        }
+    }
+}
+
+impl Error for CloudVariableError {}
+
+impl<'strings> Target<'strings, '_> {
+    /// Adds a variable whose value is shared between everyone who uses the
+    /// project online. Its name gets prefixed with `☁ ` unless it already is.
+    /// Like other variables of the stage, it can be used by every sprite.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the target is not the stage,
+    /// if the value of the variable is not a finite number or if the project
+    /// already has 10 cloud variables.
+    pub fn add_cloud_variable(
+        &mut self,
+        mut variable: Variable<'strings>,
+    ) -> Result<VariableRef<'strings>, CloudVariableError> {
+        if !self.inner.is_stage {
+            return Err(CloudVariableError::NotStage);
+        }
+        if !matches!(variable.value, Constant::Number(n) if n.is_finite()) {
+            return Err(CloudVariableError::NotNumber);
+        }
+        if self.inner.cloud_variables.len() >= MAX_CLOUD_VARIABLES {
+            return Err(CloudVariableError::TooMany);
+        }
+        if !variable.name.starts_with(CLOUD_PREFIX) {
+            variable.name.insert_str(0, CLOUD_PREFIX);
+        }
+        let variable = self.add_variable(variable);
+        self.inner.cloud_variables.push(variable.id);
+        Ok(variable)
+    }
+}
@@ -1,27 +1,35 @@
-use std::{
-    fs,
-    io::{self, Write},
-    path::Path,
-};
+use std::{borrow::Cow, fs, io, path::Path};
 
 pub struct Costume<'strings> {
-    name: &'strings str,
+    name: Cow<'strings, str>,
     data_format: Box<str>,
     digest: md5::Digest,
     content: Vec<u8>,
+    /// Only known for imported costumes.
+    rotation_center: Option<[f64; 2]>,
+    /// How many pixels of a bitmap make up one pixel on the stage,
+    /// only known for imported costumes.
+    bitmap_resolution: Option<f64>,
 }
 
 impl Costume<'_> {
     pub(crate) fn serialize(&self, writer: &mut dyn io::Write) -> io::Result<()> {
         write!(
             writer,
-            r#"{{"name":{:?},"dataFormat":{:?},"assetId":"{:x}","md5ext":"{:x}.{}"}}"#,
+            r#"{{"name":{:?},"dataFormat":{:?},"assetId":"{:x}","md5ext":"{:x}.{}""#,
             self.name,
             self.data_format,
             self.digest,
             self.digest,
             self.data_format.escape_debug()
-        )
+        )?;
+        if let Some([x, y]) = self.rotation_center {
+            write!(writer, r#","rotationCenterX":{x},"rotationCenterY":{y}"#)?;
+        }
+        if let Some(resolution) = self.bitmap_resolution {
+            write!(writer, r#","bitmapResolution":{resolution}"#)?;
+        }
+        write!(writer, "}}")
     }
 }
 
@@ -32,35 +40,42 @@ impl<'strings> Costume<'strings> {
     ///
     /// This function will return an error if the path has no extension
     /// or it fails to read the file.
-    pub fn from_file(name: &'strings str, path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn from_file(
+        name: impl Into<Cow<'strings, str>>,
+        path: &Path,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         let data_format = path
             .extension()
             .and_then(std::ffi::OsStr::to_str)
             .ok_or("costume path must have an extension")?
             .into();
         let content = fs::read(path)?;
+        Ok(Self::new(name.into(), data_format, content, None, None))
+    }
+
+    pub(crate) fn new(
+        name: Cow<'strings, str>,
+        data_format: Box<str>,
+        content: Vec<u8>,
+        rotation_center: Option<[f64; 2]>,
+        bitmap_resolution: Option<f64>,
+    ) -> Self {
         let digest = md5::compute(&content);
-        Ok(Self {
+        Self {
             name,
             data_format,
             digest,
             content,
-        })
+            rotation_center,
+            bitmap_resolution,
+        }
+    }
+
+    pub(crate) fn content(&self) -> &[u8] {
+        &self.content
     }
 
-    pub(crate) fn add_to_archive(
-        &self,
-        archive: &mut rawzip::ZipArchiveWriter<impl io::Write>,
-    ) -> Result<(), rawzip::Error> {
-        let file_name = format!("{:x}.{}", self.digest, self.data_format);
-        let (mut entry, config) = archive
-            .new_file(&file_name)
-            .compression_method(rawzip::CompressionMethod::Store)
-            .start()?;
-        let mut file = config.wrap(&mut entry);
-        file.write_all(&self.content)?;
-        let (_, descriptor) = file.finish()?;
-        let _: u64 = entry.finish(descriptor)?;
-        Ok(())
+    pub(crate) fn file_name(&self) -> String {
+        format!("{:x}.{}", self.digest, self.data_format)
     }
 }
@@ -0,0 +1,223 @@
+use crate::{
+    attach,
+    block::{self, Input, InputName},
+    InsertionPoint, Operand, Place, RealTarget, Target,
+};
+
+/// A handle to a block that has already been placed, as returned by
+/// [`Target::put`] and [`Operand::block`].
+///
+/// Handles stay valid until the blocks of the project are renumbered by
+/// [`Project::optimize`], [`Project::simplify`] or streaming. Using a handle
+/// to a block that has been streamed panics.
+///
+/// [`Project::optimize`]: crate::Project::optimize
+/// [`Project::simplify`]: crate::Project::simplify
+#[derive(Clone, Copy)]
+pub struct BlockRef {
+    block: block::Id,
+    /// The number of blocks that had been streamed when the handle was
+    /// created.
+    generation: u32,
+}
+
+impl BlockRef {
+    pub(crate) const fn new(block: block::Id, generation: u32) -> Self {
+        Self { block, generation }
+    }
+}
+
+impl Operand<'_> {
+    /// Returns the block that computes this operand,
+    /// or `None` if it is a constant, variable or list.
+    #[must_use]
+    pub const fn block(&self) -> Option<BlockRef> {
+        match self.0 {
+            Input::Reporter(block, generation) => Some(BlockRef { block, generation }),
+            _ => None,
+        }
+    }
+}
+
+impl<'strings> Target<'strings, '_> {
+    /// Returns the opcode of a block, such as `"control_repeat"`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block has been streamed.
+    #[must_use]
+    pub fn opcode(&self, block: BlockRef) -> String {
+        let block = self.inner.resolve(block);
+        format!("{:?}", self.inner.blocks[block.0].opcode)
+    }
+
+    /// Replaces an input of a block, such as `"TIMES"` of a `repeat` block.
+    /// A block that computed the previous operand is detached and can be
+    /// removed with [`Project::optimize`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block has no input with the given name or if the block
+    /// or operand has been streamed.
+    ///
+    /// [`Project::optimize`]: crate::Project::optimize
+    pub fn set_input(&mut self, block: BlockRef, name: &str, operand: Operand<'strings>) {
+        let block = self.inner.resolve(block);
+        let input = self
+            .inner
+            .input_ids(block)
+            .into_iter()
+            .find(|&id| matches!(self.inner.inputs[id].0, InputName::Static(it) if it == name))
+            .unwrap_or_else(|| panic!("block has no input named {name:?}"));
+        if let Input::Substack(old) = self.inner.inputs[input].1 {
+            self.inner.blocks[old.0].parent = None;
+        }
+        let operand = attach(self.materialize(operand.0), self.inner.block_id_offset);
+        if let Input::Substack(new) = operand {
+            self.inner.blocks[new.0].parent = Some(block);
+        }
+        self.inner.inputs[input].1 = operand;
+    }
+
+    /// Removes a block from its stack, connecting the blocks before and after
+    /// it. The block is left detached and can be removed with
+    /// [`Project::optimize`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block has been streamed, if blocks are being put inside
+    /// of it, since they would be detached along with it, or if it is a
+    /// reporter in an input, which [`Target::set_input`] can replace instead.
+    ///
+    /// [`Project::optimize`]: crate::Project::optimize
+    pub fn remove(&mut self, block: BlockRef) {
+        let block = self.inner.resolve(block);
+        let next = self.inner.blocks[block.0].next.take();
+        let previous = self.inner.unlink(block, next);
+        match self.place {
+            // Blocks that would have been put after the removed one go where
+            // it used to be instead.
+            Place::After(it) if it.0.to_u32() == block.0.to_u32() => {
+                self.place = previous.unwrap_or(Place::Nowhere);
+            }
+            // The blocks after the removed one aren't its descendants anymore.
+            Place::After(it) | Place::Inside { block: it, .. } => assert!(
+                !self.inner.is_descendant(it, block),
+                "cannot remove a block that blocks are being put inside of"
+            ),
+            Place::Nowhere => {}
+        }
+    }
+
+    /// Moves a block and all blocks after it in its stack to an
+    /// [`InsertionPoint`], in front of any blocks that were already there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block or the point has been streamed, if the block is a
+    /// reporter in an input, if the point is inside of the moved stack or if
+    /// it isn't in any script, like the point that [`Target::insert_at`]
+    /// returns before a script has been started.
+    pub fn move_stack(&mut self, block: BlockRef, to: &InsertionPoint<'strings>) {
+        let block = self.inner.resolve(block);
+        self.inner.check_generation(to.1, "insertion point");
+        let (Place::After(point) | Place::Inside { block: point, .. }) = to.0 else {
+            panic!("cannot move a stack to an insertion point outside of any script")
+        };
+        assert!(
+            !self.inner.is_descendant(point, block),
+            "cannot move a stack to an insertion point inside of it"
+        );
+        let _: Option<Place> = self.inner.unlink(block, None);
+
+        let mut last = block;
+        while let Some(next) = self.inner.blocks[last.0].next {
+            last = next;
+        }
+        let (parent, rest) = match to.0 {
+            Place::Nowhere => unreachable!(),
+            Place::After(parent) => (parent, self.inner.blocks[parent.0].next.replace(block)),
+            Place::Inside {
+                block: parent,
+                input,
+            } => {
+                let rest = match self.inner.inputs[input].1 {
+                    Input::Substack(first) => Some(first),
+                    _ => None,
+                };
+                self.inner.inputs[input].1 = Input::Substack(block);
+                (parent, rest)
+            }
+        };
+        self.inner.blocks[block.0].parent = Some(parent);
+        self.inner.blocks[last.0].next = rest;
+        if let Some(rest) = rest {
+            self.inner.blocks[rest.0].parent = Some(last);
+        }
+    }
+}
+
+impl<'strings> RealTarget<'strings> {
+    /// Returns the block of a handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block has been streamed.
+    fn resolve(&self, block: BlockRef) -> block::Id {
+        self.check_generation(block.generation, "block");
+        block.block
+    }
+
+    /// Replaces the link from the parent of a block to it with `replacement`
+    /// and detaches the block. Returns the place where the block used to be.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block is a reporter in an input of its parent, since only
+    /// the substacks of C blocks can be left empty.
+    fn unlink(
+        &mut self,
+        block: block::Id,
+        replacement: Option<block::Id>,
+    ) -> Option<Place<'strings>> {
+        let place = self.blocks[block.0].parent.and_then(|parent| {
+            if self.blocks[parent.0]
+                .next
+                .is_some_and(|next| next.0.to_u32() == block.0.to_u32())
+            {
+                return Some(Place::After(parent));
+            }
+            let input = self.input_ids(parent).into_iter().find(|&id| {
+                matches!(self.inputs[id].1, Input::Substack(it) if it.0.to_u32() == block.0.to_u32())
+            })?;
+            assert!(
+                matches!(
+                    self.inputs[input].0,
+                    InputName::Static("SUBSTACK" | "SUBSTACK2")
+                ),
+                "cannot remove or move a reporter, replace the input with `set_input` instead"
+            );
+            Some(Place::Inside {
+                block: parent,
+                input,
+            })
+        });
+
+        let parent = self.blocks[block.0].parent.take();
+        if let Some(replacement) = replacement {
+            self.blocks[replacement.0].parent = parent;
+        }
+        match place {
+            Some(Place::After(parent)) => self.blocks[parent.0].next = replacement,
+            Some(Place::Inside { input, .. }) => {
+                self.inputs[input].1 = replacement.map_or(Input::EmptySubstack, Input::Substack);
+            }
+            Some(Place::Nowhere) | None => {}
+        }
+        place
+    }
+
+    fn input_ids(&self, block: block::Id) -> tec::Range<(InputName, Input<'strings>)> {
+        self.input_ranges[block.0.to_u32() as usize]
+    }
+}
@@ -0,0 +1,352 @@
+//! Constant folding with the same semantics as the Scratch VM.
+
+#![expect(
+    clippy::float_cmp,
+    reason = "exact comparisons are needed to match JavaScript"
+)]
+
+use crate::block::{Input, Opcode};
+use std::{borrow::Cow, cmp::Ordering, fmt};
+
+/// A value in Scratch, which is converted between types like in the
+/// Scratch VM when used.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value<'strings> {
+    Number(f64),
+    String(Cow<'strings, str>),
+    Boolean(bool),
+}
+
+impl<'strings> Value<'strings> {
+    pub(crate) fn from_input(input: &Input<'strings>) -> Option<Self> {
+        match input {
+            Input::Number(n) => Some(Self::Number(*n)),
+            Input::String(s) => Some(Self::String(s.clone())),
+            Input::Boolean(b) => Some(Self::Boolean(*b)),
+            _ => None,
+        }
+    }
+
+    fn into_input(self) -> Input<'strings> {
+        match self {
+            Self::Number(n) => Input::Number(n),
+            Self::String(s) => Input::String(s),
+            Self::Boolean(b) => Input::Boolean(b),
+        }
+    }
+
+    /// Like JavaScript's `Number(value)`.
+    pub(crate) fn to_raw_number(&self) -> f64 {
+        match self {
+            Self::Number(n) => *n,
+            Self::String(s) => parse_number(s),
+            Self::Boolean(b) => f64::from(u8::from(*b)),
+        }
+    }
+
+    /// Like `Cast.toNumber` in the Scratch VM.
+    #[must_use]
+    pub fn to_number(&self) -> f64 {
+        let n = self.to_raw_number();
+        if n.is_nan() {
+            0.0
+        } else {
+            n
+        }
+    }
+
+    /// Like `Cast.toBoolean` in the Scratch VM.
+    #[must_use]
+    pub fn to_boolean(&self) -> bool {
+        match self {
+            Self::Number(n) => *n != 0.0 && !n.is_nan(),
+            Self::String(s) => !(s.is_empty() || s == "0" || s.eq_ignore_ascii_case("false")),
+            Self::Boolean(b) => *b,
+        }
+    }
+
+    /// Like `Cast.toString` in the Scratch VM.
+    pub(crate) fn into_string(self) -> Cow<'strings, str> {
+        match self {
+            Self::Number(n) => format_number(n).into(),
+            Self::String(s) => s,
+            Self::Boolean(b) => b.to_string().into(),
+        }
+    }
+
+    /// Like `Cast.isWhiteSpace` in the Scratch VM.
+    fn is_white_space(&self) -> bool {
+        matches!(self, Self::String(s) if s.trim_matches(is_js_whitespace).is_empty())
+    }
+}
+
+/// Like `Cast.toString` in the Scratch VM.
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => f.write_str(&format_number(*n)),
+            Self::String(s) => f.write_str(s),
+            Self::Boolean(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+/// Evaluates an operator block whose inputs are all constants.
+/// Returns `None` if any input is not a constant or if the operator
+/// cannot be evaluated exactly at build time.
+pub fn evaluate<'strings>(
+    opcode: Opcode,
+    inputs: &[(&str, Input<'strings>)],
+) -> Option<Input<'strings>> {
+    let args = inputs
+        .iter()
+        .map(|(_, input)| Value::from_input(input))
+        .collect::<Option<Vec<_>>>()?;
+    operate(opcode, args).map(Value::into_input)
+}
+
+/// Evaluates an operator block with the given arguments in the order of its
+/// inputs. Returns `None` if the opcode is not a pure operator or if the
+/// result cannot be represented.
+pub fn operate(opcode: Opcode, args: Vec<Value>) -> Option<Value> {
+    let mut args = args.into_iter();
+    let mut arg = || args.next().unwrap_or_else(|| unreachable!());
+
+    let result = match opcode {
+        Opcode::operator_add => Value::Number(arg().to_number() + arg().to_number()),
+        Opcode::operator_subtract => Value::Number(arg().to_number() - arg().to_number()),
+        Opcode::operator_multiply => Value::Number(arg().to_number() * arg().to_number()),
+        Opcode::operator_divide => Value::Number(arg().to_number() / arg().to_number()),
+        Opcode::operator_mod => {
+            let n = arg().to_number();
+            let modulus = arg().to_number();
+            let mut result = n % modulus;
+            if result / modulus < 0.0 {
+                result += modulus;
+            }
+            Value::Number(result)
+        }
+        Opcode::operator_lt => Value::Boolean(compare(&arg(), &arg()) == Ordering::Less),
+        Opcode::operator_equals => Value::Boolean(compare(&arg(), &arg()) == Ordering::Equal),
+        Opcode::operator_gt => Value::Boolean(compare(&arg(), &arg()) == Ordering::Greater),
+        Opcode::operator_not => Value::Boolean(!arg().to_boolean()),
+        Opcode::operator_and => Value::Boolean(arg().to_boolean() & arg().to_boolean()),
+        Opcode::operator_or => Value::Boolean(arg().to_boolean() | arg().to_boolean()),
+        Opcode::operator_join => {
+            let mut lhs = arg().into_string();
+            lhs.to_mut().push_str(&arg().into_string());
+            Value::String(lhs)
+        }
+        #[expect(clippy::cast_precision_loss, reason = "strings are never that long")]
+        Opcode::operator_length => Value::Number(arg().into_string().encode_utf16().count() as f64),
+        Opcode::operator_letter_of => {
+            let string = arg().into_string();
+            let index = arg().to_number() - 1.0;
+            let units = string.encode_utf16().collect::<Vec<_>>();
+            #[expect(clippy::cast_precision_loss, reason = "strings are never that long")]
+            if index < 0.0 || index >= units.len() as f64 {
+                Value::String("".into())
+            } else {
+                #[expect(
+                    clippy::cast_possible_truncation,
+                    clippy::cast_sign_loss,
+                    reason = "`charAt` truncates the index and it was checked to be in bounds"
+                )]
+                let unit = units[index as usize];
+                // Lone surrogates cannot be represented in a Rust string.
+                Value::String(char::from_u32(unit.into())?.to_string().into())
+            }
+        }
+        Opcode::operator_contains => {
+            let haystack = arg().into_string().to_lowercase();
+            let needle = arg().into_string().to_lowercase();
+            Value::Boolean(haystack.contains(&needle))
+        }
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+/// Evaluates a `mathop` block with a constant input.
+/// Functions whose results depend on the JavaScript engine's implementation
+/// are not evaluated.
+pub fn mathop<'strings>(operator: &str, num: &Input<'strings>) -> Option<Input<'strings>> {
+    let n = Value::from_input(num)?.to_number();
+    if matches!(
+        &*operator.to_lowercase(),
+        "asin" | "acos" | "atan" | "ln" | "log" | "e ^" | "10 ^"
+    ) {
+        return None;
+    }
+    Some(Input::Number(math(operator, n)))
+}
+
+/// Like `operator_mathop` in the Scratch VM.
+#[expect(
+    clippy::suboptimal_flops,
+    reason = "the Scratch VM converts to degrees like this"
+)]
+pub fn math(operator: &str, n: f64) -> f64 {
+    match &*operator.to_lowercase() {
+        "abs" => n.abs(),
+        "floor" => n.floor(),
+        "ceiling" => n.ceil(),
+        "sqrt" => n.sqrt(),
+        "sin" => js_round((std::f64::consts::PI * n / 180.0).sin() * 1e10) / 1e10,
+        "cos" => js_round((std::f64::consts::PI * n / 180.0).cos() * 1e10) / 1e10,
+        "tan" => {
+            let angle = n % 360.0;
+            if angle == -270.0 || angle == 90.0 {
+                f64::INFINITY
+            } else if angle == -90.0 || angle == 270.0 {
+                f64::NEG_INFINITY
+            } else {
+                js_round((std::f64::consts::PI * angle / 180.0).tan() * 1e10) / 1e10
+            }
+        }
+        "asin" => n.asin() * 180.0 / std::f64::consts::PI,
+        "acos" => n.acos() * 180.0 / std::f64::consts::PI,
+        "atan" => n.atan() * 180.0 / std::f64::consts::PI,
+        "ln" => n.ln(),
+        "log" => n.ln() / std::f64::consts::LN_10,
+        "e ^" => n.exp(),
+        "10 ^" => 10.0_f64.powf(n),
+        _ => 0.0,
+    }
+}
+
+/// Like `Cast.compare` in the Scratch VM.
+pub fn compare(lhs: &Value, rhs: &Value) -> Ordering {
+    let mut n1 = lhs.to_raw_number();
+    let mut n2 = rhs.to_raw_number();
+    if n1 == 0.0 && lhs.is_white_space() {
+        n1 = f64::NAN;
+    } else if n2 == 0.0 && rhs.is_white_space() {
+        n2 = f64::NAN;
+    }
+    n1.partial_cmp(&n2).unwrap_or_else(|| {
+        let s1 = value_to_lowercase(lhs);
+        let s2 = value_to_lowercase(rhs);
+        s1.encode_utf16().cmp(s2.encode_utf16())
+    })
+}
+
+fn value_to_lowercase(value: &Value) -> String {
+    match value {
+        Value::Number(n) => format_number(*n).to_lowercase(),
+        Value::String(s) => s.to_lowercase(),
+        Value::Boolean(b) => b.to_string(),
+    }
+}
+
+/// Like JavaScript's `Math.round`, which rounds halfway cases towards
+/// positive infinity.
+pub fn js_round(n: f64) -> f64 {
+    let rounded = n.round();
+    if n - rounded == 0.5 {
+        rounded + 1.0
+    } else {
+        rounded
+    }
+}
+
+const fn is_js_whitespace(c: char) -> bool {
+    c == '\u{feff}' || (c.is_whitespace() && c != '\u{85}')
+}
+
+/// Like JavaScript's `Number(string)`.
+fn parse_number(s: &str) -> f64 {
+    let s = s.trim_matches(is_js_whitespace);
+    if s.is_empty() {
+        return 0.0;
+    }
+
+    let radix = match s.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0o" | "0O") => 8,
+        Some("0b" | "0B") => 2,
+        _ => 10,
+    };
+    if radix != 10 {
+        let digits = &s[2..];
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return f64::NAN;
+        }
+        #[expect(clippy::cast_precision_loss, reason = "JavaScript rounds too")]
+        return u128::from_str_radix(digits, radix).map_or_else(
+            |_| {
+                #[expect(clippy::suboptimal_flops, reason = "JavaScript doesn't fuse them")]
+                digits.chars().fold(0.0, |acc, c| {
+                    acc * f64::from(radix) + f64::from(c.to_digit(radix).unwrap_or(0))
+                })
+            },
+            |n| n as f64,
+        );
+    }
+
+    let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+    if unsigned == "Infinity" {
+        return if s.starts_with('-') {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        };
+    }
+
+    // Rust also accepts things like "inf" and "NaN", which JavaScript doesn't.
+    let (mantissa, exponent) = unsigned
+        .split_once(['e', 'E'])
+        .map_or((unsigned, None), |(mantissa, exponent)| {
+            (mantissa, Some(exponent))
+        });
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    let valid_mantissa =
+        !(integer.is_empty() && fraction.is_empty()) && is_digits(integer) && is_digits(fraction);
+    let valid_exponent = exponent.is_none_or(|exponent| {
+        let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        !digits.is_empty() && is_digits(digits)
+    });
+    if valid_mantissa && valid_exponent {
+        s.parse().unwrap_or(f64::NAN)
+    } else {
+        f64::NAN
+    }
+}
+
+/// Like JavaScript's `Number.prototype.toString`.
+fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_owned();
+    }
+    if n == 0.0 {
+        return "0".to_owned();
+    }
+    if n.is_infinite() {
+        return if n < 0.0 { "-Infinity" } else { "Infinity" }.to_owned();
+    }
+
+    let sign = if n < 0.0 { "-" } else { "" };
+    // Rust already finds the shortest digits that round-trip,
+    // only the placement of the decimal point differs.
+    let scientific = format!("{:e}", n.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or_else(|| unreachable!());
+    let digits = mantissa.replace('.', "");
+    let k = i32::try_from(digits.len()).unwrap_or_else(|_| unreachable!());
+    let point = exponent.parse::<i32>().unwrap_or_else(|_| unreachable!()) + 1;
+    let zeros = |count: i32| "0".repeat(count.unsigned_abs() as usize);
+
+    if k <= point && point <= 21 {
+        format!("{sign}{digits}{}", zeros(point - k))
+    } else if 0 < point && point <= 21 {
+        let (integer, fraction) = digits.split_at(point.unsigned_abs() as usize);
+        format!("{sign}{integer}.{fraction}")
+    } else if -6 < point && point <= 0 {
+        format!("{sign}0.{}{digits}", zeros(point))
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let dot = if rest.is_empty() { "" } else { "." };
+        format!("{sign}{first}{dot}{rest}e{:+}", point - 1)
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{
+    block::{self, DeleteIndex, ListIndex},
+    BlockRef, Constant, CustomBlockRef, InsertionPoint, Operand, Parameter, Target,
+};
+
+/// Local variables that have been pushed onto the call stack, as returned by
+/// [`Target::push_frame`].
+pub struct Frame {
+    len: u32,
+    /// The number of results of functions that the C blocks around the place
+    /// where the frame was pushed keep on the call stack.
+    enclosing_results: u32,
+}
+
+impl Frame {
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    #[must_use]
+    pub fn local(&self, index: usize) -> LocalRef {
+        let index = u32::try_from(index).unwrap_or_else(|_| panic!("index out of bounds"));
+        assert!(index < self.len, "index out of bounds");
+        LocalRef {
+            offset: self.len - 1 - index,
+            enclosing_results: self.enclosing_results,
+        }
+    }
+}
+
+/// A local variable, which can be read with [`Target::local`] and written
+/// with [`Target::set_local`].
+#[derive(Clone, Copy)]
+pub struct LocalRef {
+    /// The number of items that are above the variable on the call stack.
+    offset: u32,
+    enclosing_results: u32,
+}
+
+/// A custom block with local variables, as returned by
+/// [`Target::add_custom_block_with_locals`].
+#[derive(Clone, Copy)]
+pub struct FramedBlockRef {
+    block: CustomBlockRef,
+    body: CustomBlockRef,
+}
+
+impl FramedBlockRef {
+    /// Returns the custom block that pushes the local variables and runs the
+    /// body, which can be passed to [`Target::use_custom_block`].
+    #[must_use]
+    pub const fn custom_block(self) -> CustomBlockRef {
+        self.block
+    }
+
+    /// Returns the custom block that holds the body, which can be passed to
+    /// [`Target::custom_block_parameter`] inside of the body.
+    #[must_use]
+    pub const fn body(self) -> CustomBlockRef {
+        self.body
+    }
+}
+
+impl<'strings> Target<'strings, '_> {
+    /// Adds a custom block with local variables that are pushed onto the
+    /// call stack when it is called and popped however it finishes, including
+    /// through a `stop this script` block.
+    ///
+    /// The body is put into a second custom block named `{name} body`, which
+    /// the first one calls between pushing and popping the frame. Use
+    /// [`Target::add_function`] with [`Target::push_frame`] for custom blocks
+    /// that return values instead, since [`Target::return_value`] pops the
+    /// frame by itself.
+    pub fn add_custom_block_with_locals(
+        &mut self,
+        name: String,
+        parameters: impl Iterator<Item = Parameter>,
+        initial_values: Vec<Constant<'strings>>,
+    ) -> (FramedBlockRef, Frame, InsertionPoint<'strings>) {
+        let parameters = parameters.collect::<Vec<_>>();
+        let parameter_count = parameters.len();
+        let (body, body_point) =
+            self.add_custom_block(format!("{name} body"), parameters.clone().into_iter());
+        let (block, point) = self.add_custom_block(name, parameters.into_iter());
+
+        // Results of calls in the script that is being built belong there.
+        let pending_results = std::mem::take(&mut self.pending_results);
+        let stack_offsets = std::mem::take(&mut self.stack_offsets);
+        let previous = self.insert_at(point);
+        let frame = self.push_frame(initial_values);
+        let arguments = (0..parameter_count)
+            .map(|i| self.custom_block_parameter(block, i))
+            .collect();
+        self.use_custom_block(body, arguments);
+        self.pop_frame(&frame);
+        let _: InsertionPoint = self.insert_at(previous);
+        self.pending_results = pending_results;
+        self.stack_offsets = stack_offsets;
+
+        (FramedBlockRef { block, body }, frame, body_point)
+    }
+
+    /// Pushes local variables with the given initial values onto the call
+    /// stack, usually at the start of the body of a custom block.
+    ///
+    /// Unlike variables, local variables are not shared between recursive
+    /// calls of a custom block. The call stack is a list of the target, so
+    /// clones have their own copy. Local variables are found relative to the
+    /// end of the call stack, so other scripts of the same sprite or clone
+    /// must not use local variables while this frame is on the stack, which
+    /// can happen if the custom block waits or is not run without screen
+    /// refresh.
+    ///
+    /// The frame must be popped with [`Target::pop_frame`] before every
+    /// place where the custom block finishes, except for
+    /// [`Target::return_value`], which pops it by itself.
+    /// [`Target::add_custom_block_with_locals`] does this automatically.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no script has been started.
+    pub fn push_frame(&mut self, initial_values: Vec<Constant<'strings>>) -> Frame {
+        // Results that are never used must not be removed after these.
+        self.release_results();
+        let len = u32::try_from(initial_values.len()).unwrap_or_else(|_| unreachable!());
+        for value in initial_values {
+            self.push_to_stack(match value {
+                Constant::String(s) => s.into(),
+                Constant::Number(n) => n.into(),
+            });
+        }
+        Frame {
+            len,
+            enclosing_results: self.enclosing_results,
+        }
+    }
+
+    /// Removes local variables from the call stack.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no script has been started.
+    pub fn pop_frame(&mut self, frame: &Frame) {
+        let stack = self.call_stack();
+        for _ in 0..frame.len {
+            let _: BlockRef = self.put(block::delete_of_list(stack, DeleteIndex::Last));
+        }
+    }
+
+    /// Returns an operand for the value of a local variable, which must be
+    /// used while its frame is on top of the call stack apart from the
+    /// results of functions.
+    pub fn local(&mut self, local: LocalRef) -> Operand<'strings> {
+        let offset = self.local_offset(local);
+        self.stack_item(offset)
+    }
+
+    /// Returns a block that sets a local variable, which can be placed with
+    /// [`Target::put`].
+    pub fn set_local(
+        &mut self,
+        local: LocalRef,
+        value: Operand<'strings>,
+    ) -> block::Stacking<'strings> {
+        let stack = self.call_stack();
+        let offset = self.local_offset(local);
+        let index = self.stack_index(offset);
+        block::replace(stack, ListIndex::At(index), value)
+    }
+
+    /// Counts the items above a local variable on the call stack, including
+    /// results of functions that haven't been used yet and results that C
+    /// blocks entered since the frame was pushed still use.
+    const fn local_offset(&self, local: LocalRef) -> u32 {
+        local.offset + self.pending_results + self.enclosing_results - local.enclosing_results
+    }
+}
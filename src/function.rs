@@ -0,0 +1,177 @@
+use crate::{
+    block::{self, DeleteIndex, Input, ListIndex},
+    BlockRef, CustomBlockRef, InsertionPoint, List, ListRef, Operand, Parameter, ParameterKind,
+    Target,
+};
+
+/// A custom block that returns a value, as returned by
+/// [`Target::add_function`].
+#[derive(Clone, Copy)]
+pub struct FunctionRef {
+    block: CustomBlockRef,
+    /// The index of the hidden parameter that holds the index of the item
+    /// of the call stack that the result is written to.
+    slot: usize,
+}
+
+impl FunctionRef {
+    /// Returns the custom block of the function, which can be passed to
+    /// [`Target::custom_block_parameter`]. It must only be called with
+    /// [`Target::call_function`].
+    #[must_use]
+    pub const fn custom_block(self) -> CustomBlockRef {
+        self.block
+    }
+}
+
+impl<'strings> Target<'strings, '_> {
+    /// Adds a custom block that returns a value with
+    /// [`Target::return_value`], which [`Target::call_function`] reports.
+    ///
+    /// Results are passed through a list of the target named `call stack`,
+    /// which makes functions safe to call recursively. Every call appends an
+    /// item to the list for the result and passes its index in an extra
+    /// parameter named `return slot` after the given ones.
+    pub fn add_function(
+        &mut self,
+        name: String,
+        parameters: impl Iterator<Item = Parameter>,
+    ) -> (FunctionRef, InsertionPoint<'strings>) {
+        let mut parameters = parameters.collect::<Vec<_>>();
+        let slot = parameters.len();
+        parameters.push(Parameter {
+            name: "return slot".to_owned(),
+            kind: ParameterKind::StringOrNumber,
+        });
+        let (block, body) = self.add_custom_block(name, parameters.into_iter());
+        (FunctionRef { block, slot }, body)
+    }
+
+    /// Calls a function and returns an operand for its result, which is an
+    /// empty string if the function finishes without returning a value.
+    ///
+    /// The result must be used by the next block that is placed after the
+    /// call, such as a block passed to [`Target::put`] or the condition of an
+    /// [`Target::if_`]. Several results can be used by the same block. They
+    /// are removed from the call stack after that block has run. A result
+    /// can't be used as the condition of [`Target::while_`] or
+    /// [`Target::repeat_until`] since the function would not be called again
+    /// for each iteration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no script has been started.
+    pub fn call_function(
+        &mut self,
+        function: FunctionRef,
+        mut arguments: Vec<Operand<'strings>>,
+    ) -> Operand<'strings> {
+        let stack = self.call_stack();
+        self.push_to_stack(String::new().into());
+        arguments.push(self.length_of_list(stack));
+        let call = self.call_custom_block(function.block, arguments);
+        // The arguments have been read by now, so later pushes don't move them.
+        self.stack_offsets
+            .retain(|&(block, _)| !self.inner.is_descendant(block, call));
+
+        self.pending_results += 1;
+        self.stack_item(0)
+    }
+
+    /// Returns a value from the function whose body is being built, which
+    /// stops the function like a `stop this script` block.
+    ///
+    /// Functions should return with this instead of stopping the script
+    /// because it also removes the local variables of the function and
+    /// results of calls inside of it from the call stack.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no script has been started.
+    pub fn return_value(&mut self, function: FunctionRef, value: Operand<'strings>) {
+        let stack = self.call_stack();
+        let slot = self.custom_block_parameter(function.block, function.slot);
+        let _: block::Id =
+            self.put_without_release(block::replace(stack, ListIndex::At(slot), value));
+        // They are removed along with everything else after the slot.
+        self.pending_results = 0;
+        self.stack_offsets.clear();
+
+        let length = self.length_of_list(stack);
+        let slot = self.custom_block_parameter(function.block, function.slot);
+        let count = self.sub(length, slot);
+        let after = self.repeat(count);
+        let _: BlockRef = self.put(block::delete_of_list(stack, DeleteIndex::Last));
+        let _: InsertionPoint = self.insert_at(after);
+        let _: BlockRef = self.put(block::stop_this_script());
+    }
+
+    /// Checks that the condition of a loop that is about to be placed doesn't
+    /// use the results of functions, which the loop can't call again.
+    pub(crate) fn check_loop_condition(&self) {
+        assert!(
+            self.pending_results == 0,
+            "the result of a function can't be used as the condition of a loop"
+        );
+    }
+
+    /// Removes the results of functions that the block that was just placed
+    /// has used from the call stack.
+    pub(crate) fn release_results(&mut self) {
+        self.stack_offsets.clear();
+        if self.pending_results == 0 {
+            return;
+        }
+        let stack = self.call_stack();
+        for _ in 0..std::mem::take(&mut self.pending_results) {
+            let _: block::Id =
+                self.put_without_release(block::delete_of_list(stack, DeleteIndex::Last));
+        }
+    }
+
+    /// Appends an item to the call stack, which moves the items that are read
+    /// by the blocks that haven't been placed yet further away from its end.
+    pub(crate) fn push_to_stack(&mut self, item: Operand<'strings>) {
+        let stack = self.call_stack();
+        for &(_, offset) in &self.stack_offsets {
+            if let Input::Number(offset) = &mut self.inner.inputs[offset].1 {
+                *offset += 1.0;
+            }
+        }
+        let _: block::Id = self.put_without_release(block::append(stack, item));
+    }
+
+    /// Returns the index of an item of the call stack, counted from its end.
+    pub(crate) fn stack_index(&mut self, offset: u32) -> Operand<'strings> {
+        let stack = self.call_stack();
+        let length = self.length_of_list(stack);
+        let input = self.inner.inputs.next_id().strict_add(1);
+        let index = self.sub(length, f64::from(offset).into());
+        let Operand(Input::Reporter(block, _)) = index else {
+            unreachable!()
+        };
+        self.stack_offsets.push((block, input));
+        index
+    }
+
+    pub(crate) fn stack_item(&mut self, offset: u32) -> Operand<'strings> {
+        let stack = self.call_stack();
+        let index = self.stack_index(offset);
+        self.item_of_list(stack, ListIndex::At(index))
+    }
+
+    /// Returns the sprite-local list that holds the results of functions and
+    /// local variables, which clones get their own copy of.
+    pub(crate) fn call_stack(&mut self) -> ListRef<'strings> {
+        let list = *self.inner.call_stack.get_or_insert_with(|| {
+            self.inner.lists.push(List {
+                name: "call stack".to_owned(),
+                items: Vec::new(),
+            })
+        });
+        ListRef {
+            target: self.inner.index,
+            id: list,
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use crate::{
+    Constant, Costume, List, ListRef, Project, RawBlock, RealTarget, Sound, Target, Variable,
+    VariableRef,
+};
+use serde_json::Value;
+use std::{borrow::Cow, collections::HashMap, error::Error, io};
+
+impl<'strings> Project<'strings> {
+    /// Imports a `.sprite3` file as a new sprite.
+    ///
+    /// The scripts of the sprite are kept as they are. Its variables and lists
+    /// can be used by new blocks through [`Target::find_variable`] and
+    /// [`Target::find_list`]. Broadcasts get IDs that only depend on their
+    /// names, so sprites imported from different projects can send messages
+    /// to each other. Comments and properties such as the position of the
+    /// sprite are not imported.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading from the `reader` fails,
+    /// if it doesn't contain a valid sprite or if the [`Project`] already has a
+    /// sprite with the same name.
+    pub fn import_sprite(
+        &mut self,
+        mut reader: impl io::Read,
+    ) -> Result<Target<'strings, '_>, Box<dyn Error>> {
+        let mut data = Vec::new();
+        _ = reader.read_to_end(&mut data)?;
+        let files = read_archive(&data)?;
+        let sprite = files
+            .get("sprite.json")
+            .ok_or("sprite3 file has no sprite.json")?;
+        let sprite = serde_json::from_slice::<Value>(sprite)?;
+        let index = self.next_target_index();
+        Ok(self.push_sprite(import_target(&sprite, &files, index)?)?)
+    }
+}
+
+fn read_archive(data: &[u8]) -> Result<HashMap<String, Vec<u8>>, Box<dyn Error>> {
+    let archive = rawzip::ZipArchive::from_slice(data)?;
+    let mut files = HashMap::new();
+    for entry in archive.entries() {
+        let entry = entry?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = String::from_utf8_lossy(entry.file_path().as_ref()).into_owned();
+        let data = archive.get_entry(entry.wayfinder())?.data();
+        let content = match entry.compression_method() {
+            rawzip::CompressionMethod::Store => data.to_vec(),
+            rawzip::CompressionMethod::Deflate => {
+                let mut content = Vec::new();
+                _ = io::Read::read_to_end(
+                    &mut flate2::read::DeflateDecoder::new(data),
+                    &mut content,
+                )?;
+                content
+            }
+            _ => return Err(format!("{name} uses an unsupported compression method").into()),
+        };
+        _ = files.insert(name, content);
+    }
+    Ok(files)
+}
+
+fn import_target<'strings>(
+    sprite: &Value,
+    files: &HashMap<String, Vec<u8>>,
+    index: u32,
+) -> Result<RealTarget<'strings>, Box<dyn Error>> {
+    let name = sprite["name"].as_str().ok_or("sprite has no name")?;
+    let mut target = RealTarget::new(Cow::Owned(name.to_owned()), index);
+    let mut ids = Ids::default();
+
+    for (id, variable) in object(&sprite["variables"]) {
+        let name = variable[0].as_str().ok_or("variable has no name")?;
+        let new_id = VariableRef {
+            target: index,
+            id: target.variables.push(Variable {
+                name: name.to_owned(),
+                value: constant(&variable[1]),
+            }),
+        };
+        _ = ids.variables.insert(id.clone(), new_id.serialized_id());
+    }
+
+    for (id, list) in object(&sprite["lists"]) {
+        let name = list[0].as_str().ok_or("list has no name")?;
+        let items = list[1].as_array().ok_or("list has no items")?;
+        let new_id = ListRef {
+            target: index,
+            id: target.lists.push(List {
+                name: name.to_owned(),
+                items: items.iter().map(constant).collect(),
+            }),
+        };
+        _ = ids.lists.insert(id.clone(), new_id.serialized_id());
+    }
+
+    for costume in array(&sprite["costumes"]) {
+        let asset = read_asset(costume, files)?;
+        let rotation_center = costume["rotationCenterX"]
+            .as_f64()
+            .zip(costume["rotationCenterY"].as_f64())
+            .map(<[f64; 2]>::from);
+        target.costumes.push(Costume::new(
+            asset.name,
+            asset.data_format,
+            asset.content,
+            rotation_center,
+            costume["bitmapResolution"].as_f64(),
+        ));
+    }
+
+    for sound in array(&sprite["sounds"]) {
+        let asset = read_asset(sound, files)?;
+        let rate = sound["rate"]
+            .as_u64()
+            .and_then(|rate| u32::try_from(rate).ok())
+            .ok_or("sound has no rate")?;
+        let sample_count = sound["sampleCount"]
+            .as_u64()
+            .ok_or("sound has no sample count")?;
+        target.sounds.push(Sound::new(
+            asset.name,
+            asset.data_format,
+            asset.content,
+            rate,
+            sample_count,
+        ));
+    }
+
+    // Generated block IDs start with a "b", so these can't collide with them.
+    let blocks = object(&sprite["blocks"]).collect::<Vec<_>>();
+    for (i, (id, _)) in blocks.iter().enumerate() {
+        _ = ids.blocks.insert((*id).clone(), format!("i{i}"));
+    }
+    for (_, block) in blocks {
+        let mut block = block.clone();
+        ids.remap_block(&mut block);
+        target.raw_blocks.push(RawBlock {
+            opcode: block["opcode"].as_str().map(str::to_owned),
+            json: block.to_string(),
+        });
+    }
+
+    Ok(target)
+}
+
+fn object(value: &Value) -> impl Iterator<Item = (&String, &Value)> {
+    value.as_object().into_iter().flatten()
+}
+
+fn array(value: &Value) -> impl Iterator<Item = &Value> {
+    value.as_array().into_iter().flatten()
+}
+
+fn constant<'strings>(value: &Value) -> Constant<'strings> {
+    match value {
+        Value::Number(n) => Constant::Number(n.as_f64().unwrap_or(f64::NAN)),
+        Value::String(s) => Constant::String(Cow::Owned(s.clone())),
+        other => Constant::String(Cow::Owned(other.to_string())),
+    }
+}
+
+struct Asset<'strings> {
+    name: Cow<'strings, str>,
+    data_format: Box<str>,
+    content: Vec<u8>,
+}
+
+fn read_asset<'strings>(
+    asset: &Value,
+    files: &HashMap<String, Vec<u8>>,
+) -> Result<Asset<'strings>, Box<dyn Error>> {
+    let name = asset["name"].as_str().ok_or("asset has no name")?;
+    let data_format = asset["dataFormat"]
+        .as_str()
+        .ok_or("asset has no data format")?;
+    let file_name = asset["md5ext"].as_str().map_or_else(
+        || {
+            format!(
+                "{}.{data_format}",
+                asset["assetId"].as_str().unwrap_or_default()
+            )
+        },
+        str::to_owned,
+    );
+    let content = files
+        .get(&file_name)
+        .ok_or_else(|| format!("sprite3 file has no {file_name}"))?;
+    Ok(Asset {
+        name: Cow::Owned(name.to_owned()),
+        data_format: data_format.into(),
+        content: content.clone(),
+    })
+}
+
+/// Maps the IDs used in the imported sprite to new ones.
+#[derive(Default)]
+struct Ids {
+    blocks: HashMap<String, String>,
+    variables: HashMap<String, String>,
+    lists: HashMap<String, String>,
+}
+
+impl Ids {
+    fn remap_block(&self, block: &mut Value) {
+        match block {
+            Value::Array(primitive) => self.remap_primitive(primitive),
+            Value::Object(block) => {
+                for key in ["parent", "next"] {
+                    if let Some(Value::String(id)) = block.get_mut(key) {
+                        remap(&self.blocks, id);
+                    }
+                }
+                if let Some(Value::Object(inputs)) = block.get_mut("inputs") {
+                    for input in inputs.values_mut().filter_map(Value::as_array_mut) {
+                        // The first element is the shadow type.
+                        for value in input.iter_mut().skip(1) {
+                            match value {
+                                Value::String(id) => remap(&self.blocks, id),
+                                Value::Array(primitive) => self.remap_primitive(primitive),
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                if let Some(Value::Object(fields)) = block.get_mut("fields") {
+                    for (key, ids) in [("VARIABLE", &self.variables), ("LIST", &self.lists)] {
+                        if let Some(Value::String(id)) =
+                            fields.get_mut(key).and_then(|field| field.get_mut(1))
+                        {
+                            remap(ids, id);
+                        }
+                    }
+                    if let Some(Value::Array(field)) = fields.get_mut("BROADCAST_OPTION") {
+                        remap_broadcast(field);
+                    }
+                }
+                // Comments are not imported.
+                _ = block.remove("comment");
+            }
+            _ => {}
+        }
+    }
+
+    fn remap_primitive(&self, primitive: &mut [Value]) {
+        let ids = match primitive.first().and_then(Value::as_u64) {
+            Some(11) => return remap_broadcast(&mut primitive[1..]),
+            Some(12) => &self.variables,
+            Some(13) => &self.lists,
+            _ => return,
+        };
+        if let Some(Value::String(id)) = primitive.get_mut(2) {
+            remap(ids, id);
+        }
+    }
+}
+
+/// Replaces the ID of a broadcast given as `[name, id]` with one that is
+/// derived from its name. Generated blocks refer to broadcasts by name only.
+fn remap_broadcast(broadcast: &mut [Value]) {
+    if let [Value::String(name), id, ..] = broadcast {
+        *id = Value::String(format!("m{name}"));
+    }
+}
+
+fn remap(ids: &HashMap<String, String>, id: &mut String) {
+    if let Some(new_id) = ids.get(id) {
+        id.clone_from(new_id);
+    }
+}
@@ -0,0 +1,1022 @@
+//! Runs projects without a browser so that tests can check what generated
+//! scripts do.
+//!
+//! The interpreter covers the data, operator, control, event and custom block
+//! categories with the same type conversions and list semantics as the
+//! Scratch VM. Time is virtual: every frame advances the clock by 1/30 of a
+//! second, so `wait` blocks and the timer behave as if the project ran at
+//! full speed without taking any real time.
+//!
+//! Blocks from other categories only request a redraw, and reporters from them
+//! report `0`, except for `say`, `timer` and `key pressed?`.
+
+#![expect(
+    clippy::float_cmp,
+    reason = "exact comparisons are needed to match JavaScript"
+)]
+
+pub use crate::fold::Value;
+
+use crate::{
+    block::{Block, Fields, Input, InputName, Opcode},
+    fold, BlockEntry, Constant, ListRef, Parameter, Project, RealTarget, VariableRef,
+};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    error::Error,
+};
+
+/// The length of a frame in seconds, like in the Scratch VM.
+const FRAME: f64 = 1.0 / 30.0;
+/// Stands in for the time budget of a frame in the Scratch VM.
+/// Threads are stepped repeatedly until a redraw is requested, every thread
+/// is waiting or this many ticks have passed.
+const TICKS_PER_FRAME: usize = 100;
+/// Stands in for the warp timer of the Scratch VM, which makes threads in
+/// warp mode yield after half a second.
+const WARP_BLOCK_LIMIT: usize = 1_000_000;
+const LIST_LIMIT: usize = 200_000;
+const CLONE_LIMIT: usize = 300;
+
+/// Runs the scripts of a [`Project`].
+///
+/// ```ignore
+/// let mut interpreter = Interpreter::new(&project)?;
+/// interpreter.green_flag();
+/// assert!(interpreter.run(10.0));
+/// assert_eq!(interpreter.variable("Stage", "result"), Some(&Value::Number(42.0)));
+/// ```
+pub struct Interpreter<'project, 'strings> {
+    code: Vec<Code<'project, 'strings>>,
+    world: World<'strings>,
+    threads: Vec<Thread<'strings>>,
+}
+
+struct Code<'project, 'strings> {
+    target: &'project RealTarget<'strings>,
+    blocks: Vec<BlockEntry<'project, 'strings>>,
+    /// The definition of each custom block, indexed like `custom_blocks`.
+    definitions: Vec<Option<tec::Id<Block>>>,
+}
+
+struct World<'strings> {
+    /// Targets are followed by their clones.
+    instances: Vec<Instance<'strings>>,
+    target_count: usize,
+    /// The ID of the thread running each hat of each instance.
+    running: HashMap<(usize, u32), u64>,
+    /// Threads that were started while stepping another thread.
+    started: Vec<Thread<'strings>>,
+    next_thread_id: u64,
+    stop_all: bool,
+    redraw_requested: bool,
+    /// Whether anything but checking a pending wait happened this tick.
+    progress: bool,
+    clock: f64,
+    timer_start: f64,
+    rng: u64,
+    keys: HashSet<String>,
+}
+
+struct Instance<'strings> {
+    target: usize,
+    variables: Vec<Value<'strings>>,
+    lists: Vec<Vec<Value<'strings>>>,
+    speech: Option<Value<'strings>>,
+}
+
+struct Thread<'strings> {
+    id: u64,
+    instance: usize,
+    hat: tec::Id<Block>,
+    frames: Vec<Frame<'strings>>,
+}
+
+struct Frame<'strings> {
+    /// The block to run next, or `None` at the end of the stack.
+    block: Option<tec::Id<Block>>,
+    /// The progress of the loop or wait block at `block`.
+    state: Option<State>,
+    /// Whether the block that started this stack runs again after it.
+    is_loop: bool,
+    warp: bool,
+    /// The arguments of the custom block whose body this frame runs.
+    arguments: Option<Vec<(tec::Id<Parameter>, Value<'strings>)>>,
+}
+
+enum State {
+    Repeat(f64),
+    ForEach(f64),
+    Wait(f64),
+    Threads(Vec<u64>),
+}
+
+enum Flow {
+    Continue,
+    /// Lets other threads run.
+    Yield,
+    /// Like `Yield` but nothing has happened.
+    Wait,
+    Done,
+}
+
+enum ListIndex {
+    Item(usize),
+    All,
+    Invalid,
+}
+
+impl<'project, 'strings> Interpreter<'project, 'strings> {
+    /// Prepares to run a project. No scripts are running until
+    /// [`Interpreter::green_flag`] is called.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if [`Project::validate`] fails or
+    /// if the project contains streamed or imported blocks, which cannot be
+    /// interpreted.
+    pub fn new(project: &'project Project<'strings>) -> Result<Self, Box<dyn Error>> {
+        project.validate()?;
+        let mut code = Vec::new();
+        let mut instances = Vec::new();
+        for (index, target) in project.targets.iter().enumerate() {
+            if !target.streamed_blocks.is_empty() {
+                return Err(
+                    format!("cannot interpret streamed blocks of {:?}", target.name).into(),
+                );
+            }
+            if !target.raw_blocks.is_empty() {
+                return Err(
+                    format!("cannot interpret imported blocks of {:?}", target.name).into(),
+                );
+            }
+
+            let blocks = target.blocks_with_data().collect::<Vec<_>>();
+            let mut definitions = vec![None; target.custom_blocks.len()];
+            for entry in &blocks {
+                if !matches!(entry.block.opcode, Opcode::procedures_definition) {
+                    continue;
+                }
+                let Some(Input::Prototype(prototype)) = entry.inputs.first().map(|(_, it)| it)
+                else {
+                    continue;
+                };
+                if let Some(mutation) = blocks[prototype.0.to_u32() as usize].mutation {
+                    definitions[mutation.0 .0.to_u32() as usize] = Some(entry.id);
+                }
+            }
+            code.push(Code {
+                target,
+                blocks,
+                definitions,
+            });
+
+            instances.push(Instance {
+                target: index,
+                variables: target
+                    .variables
+                    .values()
+                    .map(|it| constant(&it.value))
+                    .collect(),
+                lists: target
+                    .lists
+                    .values()
+                    .map(|it| it.items.iter().map(constant).collect())
+                    .collect(),
+                speech: None,
+            });
+        }
+
+        Ok(Self {
+            code,
+            world: World {
+                target_count: instances.len(),
+                instances,
+                running: HashMap::new(),
+                started: Vec::new(),
+                next_thread_id: 0,
+                stop_all: false,
+                redraw_requested: false,
+                progress: false,
+                clock: 0.0,
+                timer_start: 0.0,
+                rng: 0x2545_f491_4f6c_dd1d,
+                keys: HashSet::new(),
+            },
+            threads: Vec::new(),
+        })
+    }
+
+    /// Stops all scripts and starts the ones under `when flag clicked`.
+    pub fn green_flag(&mut self) {
+        self.stop_all();
+        self.world.timer_start = self.world.clock;
+        let _: Vec<u64> = self.world.start_hats(&self.code, None, true, |entry| {
+            matches!(entry.block.opcode, Opcode::event_whenflagclicked)
+        });
+        self.adopt_started_threads();
+    }
+
+    /// Starts the scripts under `when I receive` for the message,
+    /// restarting the ones that are already running.
+    pub fn broadcast(&mut self, message: &str) {
+        let _: Vec<u64> = self.world.broadcast(&self.code, message);
+        self.adopt_started_threads();
+    }
+
+    /// Holds down a key, such as `"space"` or `"a"`, and starts the scripts
+    /// under `when key pressed` for it that aren't already running.
+    pub fn press_key(&mut self, key: &str) {
+        let key = key.to_lowercase();
+        let _: Vec<u64> = self.world.start_hats(&self.code, None, false, |entry| {
+            matches!(entry.fields, Some(Fields::KeyOption(it)) if it.eq_ignore_ascii_case(&key) || it == "any")
+        });
+        _ = self.world.keys.insert(key);
+        self.adopt_started_threads();
+    }
+
+    pub fn release_key(&mut self, key: &str) {
+        _ = self.world.keys.remove(&key.to_lowercase());
+    }
+
+    /// Stops all scripts and deletes all clones.
+    pub fn stop_all(&mut self) {
+        self.threads.clear();
+        self.world.stop_all();
+    }
+
+    /// Runs one frame and advances the clock.
+    pub fn step(&mut self) {
+        self.world.redraw_requested = false;
+        for _ in 0..TICKS_PER_FRAME {
+            self.world.progress = false;
+            self.tick();
+            if self.threads.is_empty() || self.world.redraw_requested || !self.world.progress {
+                break;
+            }
+        }
+        self.world.clock += FRAME;
+    }
+
+    /// Runs frames until all scripts have finished or the given number of
+    /// seconds has passed on the virtual clock.
+    /// Returns whether all scripts have finished.
+    pub fn run(&mut self, seconds: f64) -> bool {
+        let end = self.world.clock + seconds;
+        while self.is_running() && self.world.clock < end {
+            self.step();
+        }
+        !self.is_running()
+    }
+
+    #[must_use]
+    pub const fn is_running(&self) -> bool {
+        !self.threads.is_empty()
+    }
+
+    /// The number of seconds on the virtual clock.
+    #[must_use]
+    pub const fn time(&self) -> f64 {
+        self.world.clock
+    }
+
+    /// Seeds the random number generator used by `pick random`,
+    /// which is seeded with a fixed number by default.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.world.rng = seed.max(1);
+    }
+
+    /// Returns the value of a variable of the sprite or stage with the given
+    /// name. Clones have their own variables, which can't be inspected.
+    #[must_use]
+    pub fn variable(&self, target: &str, name: &str) -> Option<&Value<'strings>> {
+        let index = self.find_target(target)?;
+        let (id, _) = IntoIterator::into_iter(&self.code[index].target.variables)
+            .find(|(_, variable)| variable.name == name)?;
+        self.world.instances[index]
+            .variables
+            .get(id.to_u32() as usize)
+    }
+
+    /// Returns the items of a list of the sprite or stage with the given name.
+    #[must_use]
+    pub fn list(&self, target: &str, name: &str) -> Option<&[Value<'strings>]> {
+        let index = self.find_target(target)?;
+        let (id, _) = IntoIterator::into_iter(&self.code[index].target.lists)
+            .find(|(_, list)| list.name == name)?;
+        self.world.instances[index]
+            .lists
+            .get(id.to_u32() as usize)
+            .map(Vec::as_slice)
+    }
+
+    /// Returns what the sprite with the given name is saying, if anything.
+    #[must_use]
+    pub fn speech(&self, target: &str) -> Option<&Value<'strings>> {
+        self.world.instances[self.find_target(target)?]
+            .speech
+            .as_ref()
+    }
+
+    fn find_target(&self, name: &str) -> Option<usize> {
+        self.code.iter().position(|it| it.target.name == name)
+    }
+
+    /// Steps every thread once.
+    fn tick(&mut self) {
+        let mut i = 0;
+        while i < self.threads.len() {
+            let done = self.threads[i].step(&self.code, &mut self.world);
+            if self.world.stop_all {
+                self.stop_all();
+                return;
+            }
+            if done {
+                let thread = self.threads.remove(i);
+                self.world.running.retain(|_, id| *id != thread.id);
+            } else {
+                i += 1;
+            }
+            self.adopt_started_threads();
+        }
+    }
+
+    /// Threads that restart a running thread take its place.
+    fn adopt_started_threads(&mut self) {
+        for thread in self.world.started.drain(..) {
+            _ = self
+                .world
+                .running
+                .insert((thread.instance, thread.hat.to_u32()), thread.id);
+            if let Some(existing) = self.threads.iter_mut().find(|it| it.id == thread.id) {
+                *existing = thread;
+            } else {
+                self.threads.push(thread);
+            }
+        }
+    }
+}
+
+impl<'strings> World<'strings> {
+    fn stop_all(&mut self) {
+        self.running.clear();
+        self.started.clear();
+        self.stop_all = false;
+        self.instances.truncate(self.target_count);
+        for instance in &mut self.instances {
+            instance.speech = None;
+        }
+    }
+
+    /// Starts a thread for every top-level block that matches, in the given
+    /// instance or in every instance. Returns the IDs of the threads.
+    fn start_hats(
+        &mut self,
+        code: &[Code<'_, 'strings>],
+        instance: Option<usize>,
+        restart: bool,
+        matches: impl Fn(&BlockEntry<'_, 'strings>) -> bool,
+    ) -> Vec<u64> {
+        let instances = instance.map_or(0..self.instances.len(), |it| it..it + 1);
+        let mut started = Vec::new();
+        for instance in instances {
+            let code = &code[self.instances[instance].target];
+            for entry in &code.blocks {
+                if entry.block.parent.is_some() || !matches(entry) {
+                    continue;
+                }
+                let id = match self.running.get(&(instance, entry.id.to_u32())) {
+                    Some(_) if !restart => continue,
+                    Some(&id) => id,
+                    None => {
+                        self.next_thread_id += 1;
+                        self.next_thread_id
+                    }
+                };
+                started.push(id);
+                self.started.push(Thread {
+                    id,
+                    instance,
+                    hat: entry.id,
+                    frames: Vec::from([Frame {
+                        block: entry.block.next.map(|it| it.0),
+                        state: None,
+                        is_loop: false,
+                        warp: false,
+                        arguments: None,
+                    }]),
+                });
+            }
+        }
+        started
+    }
+
+    fn broadcast(&mut self, code: &[Code<'_, 'strings>], message: &str) -> Vec<u64> {
+        let message = message.to_lowercase();
+        self.start_hats(code, None, true, |entry| {
+            matches!(entry.fields, Some(Fields::BroadcastOption(it)) if it.to_lowercase() == message)
+        })
+    }
+
+    fn is_running(&self, id: u64) -> bool {
+        self.running.values().any(|&it| it == id)
+            || self.started.iter().any(|thread| thread.id == id)
+    }
+
+    /// Like `Math.random` but deterministic.
+    #[expect(clippy::cast_precision_loss, reason = "53 bits fit exactly")]
+    fn random(&mut self) -> f64 {
+        // xorshift64*
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        (self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Returns a variable used by the scripts of an instance, which belongs
+    /// to the stage if it is a variable of the stage.
+    fn variable(&mut self, instance: usize, variable: VariableRef) -> &mut Value<'strings> {
+        let instance = if variable.target == 0 { 0 } else { instance };
+        &mut self.instances[instance].variables[variable.id.to_u32() as usize]
+    }
+
+    /// Returns a list used by the scripts of an instance like
+    /// [`World::variable`].
+    fn list(&mut self, instance: usize, list: ListRef) -> &mut Vec<Value<'strings>> {
+        let instance = if list.target == 0 { 0 } else { instance };
+        &mut self.instances[instance].lists[list.id.to_u32() as usize]
+    }
+
+    /// Like `Cast.toListIndex` in the Scratch VM.
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss,
+        reason = "the index is checked to be in bounds"
+    )]
+    fn list_index(&mut self, index: &Value, length: usize, accept_all: bool) -> ListIndex {
+        if let Value::String(keyword) = index {
+            if keyword == "all" {
+                return if accept_all {
+                    ListIndex::All
+                } else {
+                    ListIndex::Invalid
+                };
+            }
+            let item = match &**keyword {
+                "last" => Some(length.checked_sub(1)),
+                "random" | "any" => {
+                    Some((length > 0).then(|| (self.random() * length as f64) as usize))
+                }
+                _ => None,
+            };
+            if let Some(item) = item {
+                return item.map_or(ListIndex::Invalid, ListIndex::Item);
+            }
+        }
+        let index = index.to_number().floor();
+        if index < 1.0 || index > length as f64 {
+            ListIndex::Invalid
+        } else {
+            ListIndex::Item(index as usize - 1)
+        }
+    }
+}
+
+impl<'strings> Thread<'strings> {
+    /// Runs the thread until it yields. Returns whether it has finished.
+    fn step(&mut self, codes: &[Code<'_, 'strings>], world: &mut World<'strings>) -> bool {
+        let code = &codes[world.instances[self.instance].target];
+        for _ in 0..WARP_BLOCK_LIMIT {
+            let Some(frame) = self.frames.last() else {
+                return true;
+            };
+            let Some(block) = frame.block else {
+                let frame = self.frames.pop().unwrap_or_else(|| unreachable!());
+                let Some(parent) = self.frames.last_mut() else {
+                    return true;
+                };
+                if frame.is_loop {
+                    // The loop block runs again and decides whether to go on.
+                    if !parent.warp {
+                        world.progress = true;
+                        return false;
+                    }
+                } else {
+                    self.advance(code);
+                }
+                continue;
+            };
+            match self.execute(codes, world, &code.blocks[block.to_u32() as usize]) {
+                Flow::Continue => world.progress = true,
+                Flow::Yield => {
+                    world.progress = true;
+                    return false;
+                }
+                Flow::Wait => return false,
+                Flow::Done => return true,
+            }
+        }
+        false
+    }
+
+    /// Moves past the block that the innermost frame is at.
+    fn advance(&mut self, code: &Code) {
+        let frame = self.frames.last_mut().unwrap_or_else(|| unreachable!());
+        if let Some(block) = frame.block {
+            frame.block = code.blocks[block.to_u32() as usize]
+                .block
+                .next
+                .map(|it| it.0);
+        }
+    }
+
+    fn state(&mut self) -> &mut Option<State> {
+        &mut self
+            .frames
+            .last_mut()
+            .unwrap_or_else(|| unreachable!())
+            .state
+    }
+
+    fn start_branch(&mut self, entry: &BlockEntry, name: &str, is_loop: bool) -> Flow {
+        let block = match entry.input(name) {
+            Input::Substack(it) => Some(it.0),
+            _ => None,
+        };
+        let warp = self.frames.last().is_some_and(|it| it.warp);
+        self.frames.push(Frame {
+            block,
+            state: None,
+            is_loop,
+            warp,
+            arguments: None,
+        });
+        Flow::Continue
+    }
+
+    fn execute(
+        &mut self,
+        codes: &[Code<'_, 'strings>],
+        world: &mut World<'strings>,
+        entry: &BlockEntry<'_, 'strings>,
+    ) -> Flow {
+        let code = &codes[world.instances[self.instance].target];
+        match entry.block.opcode {
+            Opcode::control_forever
+            | Opcode::control_repeat
+            | Opcode::control_for_each
+            | Opcode::control_if
+            | Opcode::control_if_else
+            | Opcode::control_while
+            | Opcode::control_repeat_until
+            | Opcode::control_wait
+            | Opcode::control_stop
+            | Opcode::control_create_clone_of => return self.control(codes, world, entry),
+            Opcode::data_setvariableto
+            | Opcode::data_changevariableby
+            | Opcode::data_addtolist
+            | Opcode::data_deleteoflist
+            | Opcode::data_deletealloflist
+            | Opcode::data_insertatlist
+            | Opcode::data_replaceitemoflist => self.data(code, world, entry),
+            Opcode::event_broadcastandwait => {
+                let Some(State::Threads(ids)) = self.state().take() else {
+                    let message = self.evaluate_input(code, world, entry, "BROADCAST_INPUT");
+                    let ids = world.broadcast(codes, &message.to_string());
+                    if ids.is_empty() {
+                        self.advance(code);
+                        return Flow::Continue;
+                    }
+                    *self.state() = Some(State::Threads(ids));
+                    return Flow::Yield;
+                };
+                if ids.iter().any(|&id| world.is_running(id)) {
+                    *self.state() = Some(State::Threads(ids));
+                    return Flow::Wait;
+                }
+            }
+            Opcode::procedures_call => return self.call(code, world, entry),
+            Opcode::looks_say => {
+                let message = self.evaluate_input(code, world, entry, "MESSAGE");
+                world.instances[self.instance].speech =
+                    Some(message).filter(|it| !it.to_string().is_empty());
+                world.redraw_requested = true;
+            }
+            Opcode::looks_sayforsecs => {
+                if let Some(State::Wait(until)) = *self.state() {
+                    if world.clock < until {
+                        return Flow::Wait;
+                    }
+                    *self.state() = None;
+                    world.instances[self.instance].speech = None;
+                } else {
+                    let message = self.evaluate_input(code, world, entry, "MESSAGE");
+                    let seconds = self.evaluate_input(code, world, entry, "SECS").to_number();
+                    world.instances[self.instance].speech =
+                        Some(message).filter(|it| !it.to_string().is_empty());
+                    *self.state() = Some(State::Wait(world.clock + seconds.max(0.0)));
+                    world.redraw_requested = true;
+                    return Flow::Yield;
+                }
+            }
+            Opcode::sensing_resettimer => world.timer_start = world.clock,
+            Opcode::sensing_askandwait => {}
+            _ => world.redraw_requested = true,
+        }
+        self.advance(code);
+        Flow::Continue
+    }
+
+    fn control(
+        &mut self,
+        codes: &[Code<'_, 'strings>],
+        world: &mut World<'strings>,
+        entry: &BlockEntry<'_, 'strings>,
+    ) -> Flow {
+        let code = &codes[world.instances[self.instance].target];
+        match entry.block.opcode {
+            Opcode::control_forever => return self.start_branch(entry, "SUBSTACK", true),
+            Opcode::control_repeat => {
+                let times = self.evaluate_input(code, world, entry, "TIMES");
+                let remaining = match self.state().take() {
+                    Some(State::Repeat(remaining)) => remaining,
+                    _ => fold::js_round(times.to_number()),
+                };
+                if remaining >= 1.0 {
+                    *self.state() = Some(State::Repeat(remaining - 1.0));
+                    return self.start_branch(entry, "SUBSTACK", true);
+                }
+            }
+            Opcode::control_for_each => {
+                let Some(&Fields::Variable(variable)) = entry.fields else {
+                    unreachable!()
+                };
+                let times = self.evaluate_input(code, world, entry, "VALUE");
+                let index = match self.state().take() {
+                    Some(State::ForEach(index)) => index,
+                    _ => 0.0,
+                };
+                if index < times.to_raw_number() {
+                    *self.state() = Some(State::ForEach(index + 1.0));
+                    *world.variable(self.instance, variable) = Value::Number(index + 1.0);
+                    return self.start_branch(entry, "SUBSTACK", true);
+                }
+            }
+            Opcode::control_if => {
+                if self
+                    .evaluate_input(code, world, entry, "CONDITION")
+                    .to_boolean()
+                {
+                    return self.start_branch(entry, "SUBSTACK", false);
+                }
+            }
+            Opcode::control_if_else => {
+                let condition = self.evaluate_input(code, world, entry, "CONDITION");
+                let branch = if condition.to_boolean() {
+                    "SUBSTACK"
+                } else {
+                    "SUBSTACK2"
+                };
+                return self.start_branch(entry, branch, false);
+            }
+            Opcode::control_while | Opcode::control_repeat_until => {
+                let condition = self.evaluate_input(code, world, entry, "CONDITION");
+                let until = matches!(entry.block.opcode, Opcode::control_repeat_until);
+                if condition.to_boolean() != until {
+                    return self.start_branch(entry, "SUBSTACK", true);
+                }
+            }
+            Opcode::control_wait => {
+                if let Some(State::Wait(until)) = *self.state() {
+                    if world.clock < until {
+                        return Flow::Wait;
+                    }
+                    *self.state() = None;
+                } else {
+                    let seconds = self.evaluate_input(code, world, entry, "DURATION");
+                    *self.state() = Some(State::Wait(world.clock + seconds.to_number().max(0.0)));
+                    world.redraw_requested = true;
+                    return Flow::Yield;
+                }
+            }
+            Opcode::control_stop => {
+                if matches!(entry.fields, Some(Fields::StopAll)) {
+                    world.stop_all = true;
+                    return Flow::Done;
+                }
+                // Stopping a custom block returns from it.
+                while let Some(frame) = self.frames.pop() {
+                    if frame.arguments.is_some() {
+                        self.advance(code);
+                        return Flow::Continue;
+                    }
+                }
+                return Flow::Done;
+            }
+            Opcode::control_create_clone_of => self.create_clone(codes, world),
+            _ => unreachable!(),
+        }
+        self.advance(code);
+        Flow::Continue
+    }
+
+    fn create_clone(&self, codes: &[Code<'_, 'strings>], world: &mut World<'strings>) {
+        let instance = &world.instances[self.instance];
+        let clones = world.instances.len() - world.target_count;
+        if codes[instance.target].target.is_stage || clones >= CLONE_LIMIT {
+            return;
+        }
+        let clone = Instance {
+            target: instance.target,
+            variables: instance.variables.clone(),
+            lists: instance.lists.clone(),
+            speech: None,
+        };
+        world.instances.push(clone);
+        let _: Vec<u64> =
+            world.start_hats(codes, Some(world.instances.len() - 1), false, |entry| {
+                matches!(entry.block.opcode, Opcode::control_start_as_clone)
+            });
+    }
+
+    fn data(
+        &self,
+        code: &Code<'_, 'strings>,
+        world: &mut World<'strings>,
+        entry: &BlockEntry<'_, 'strings>,
+    ) {
+        if let Some(&Fields::Variable(variable)) = entry.fields {
+            let value = self.evaluate_input(code, world, entry, "VALUE");
+            let variable = world.variable(self.instance, variable);
+            *variable = if matches!(entry.block.opcode, Opcode::data_changevariableby) {
+                Value::Number(variable.to_number() + value.to_number())
+            } else {
+                value
+            };
+            return;
+        }
+
+        let Some(&Fields::List(list)) = entry.fields else {
+            unreachable!()
+        };
+        match entry.block.opcode {
+            Opcode::data_addtolist => {
+                let item = self.evaluate_input(code, world, entry, "ITEM");
+                let list = world.list(self.instance, list);
+                if list.len() < LIST_LIMIT {
+                    list.push(item);
+                }
+            }
+            Opcode::data_deleteoflist => {
+                let index = self.evaluate_input(code, world, entry, "INDEX");
+                let length = world.list(self.instance, list).len();
+                let list = match world.list_index(&index, length, true) {
+                    ListIndex::Item(index) => {
+                        let _: Value = world.list(self.instance, list).remove(index);
+                        return;
+                    }
+                    ListIndex::All => world.list(self.instance, list),
+                    ListIndex::Invalid => return,
+                };
+                list.clear();
+            }
+            Opcode::data_deletealloflist => world.list(self.instance, list).clear(),
+            Opcode::data_insertatlist => {
+                let item = self.evaluate_input(code, world, entry, "ITEM");
+                let index = self.evaluate_input(code, world, entry, "INDEX");
+                let length = world.list(self.instance, list).len();
+                if let ListIndex::Item(index) = world.list_index(&index, length + 1, false) {
+                    let list = world.list(self.instance, list);
+                    if list.len() < LIST_LIMIT {
+                        list.insert(index, item);
+                    }
+                }
+            }
+            Opcode::data_replaceitemoflist => {
+                let index = self.evaluate_input(code, world, entry, "INDEX");
+                let item = self.evaluate_input(code, world, entry, "ITEM");
+                let length = world.list(self.instance, list).len();
+                if let ListIndex::Item(index) = world.list_index(&index, length, false) {
+                    world.list(self.instance, list)[index] = item;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn call(
+        &mut self,
+        code: &Code<'_, 'strings>,
+        world: &mut World<'strings>,
+        entry: &BlockEntry<'_, 'strings>,
+    ) -> Flow {
+        let custom_block = entry.mutation.unwrap_or_else(|| unreachable!()).0 .0;
+        let Some(definition) = code.definitions[custom_block.to_u32() as usize] else {
+            self.advance(code);
+            return Flow::Continue;
+        };
+        let arguments = entry
+            .inputs
+            .iter()
+            .map(|(name, input)| {
+                let InputName::Parameter(parameter) = *name else {
+                    unreachable!()
+                };
+                (parameter, self.evaluate(code, world, input))
+            })
+            .collect();
+        self.frames.push(Frame {
+            block: code.blocks[definition.to_u32() as usize]
+                .block
+                .next
+                .map(|it| it.0),
+            state: None,
+            is_loop: false,
+            // Custom blocks are always written with "run without screen
+            // refresh" enabled.
+            warp: true,
+            arguments: Some(arguments),
+        });
+        Flow::Continue
+    }
+
+    fn evaluate_input(
+        &self,
+        code: &Code<'_, 'strings>,
+        world: &mut World<'strings>,
+        entry: &BlockEntry<'_, 'strings>,
+        name: &str,
+    ) -> Value<'strings> {
+        self.evaluate(code, world, entry.input(name))
+    }
+
+    fn evaluate(
+        &self,
+        code: &Code<'_, 'strings>,
+        world: &mut World<'strings>,
+        input: &Input<'strings>,
+    ) -> Value<'strings> {
+        match input {
+            Input::Substack(block) => {
+                self.report(code, world, &code.blocks[block.0.to_u32() as usize])
+            }
+            Input::Number(n) => Value::Number(*n),
+            Input::String(s) => Value::String(s.clone()),
+            Input::Boolean(b) => Value::Boolean(*b),
+            Input::Variable(variable) => world.variable(self.instance, *variable).clone(),
+            Input::List(list) => list_contents(world.list(self.instance, *list)),
+            Input::EmptySubstack | Input::Prototype(_) => Value::String("".into()),
+            Input::Reporter(..) => unreachable!(),
+        }
+    }
+
+    fn report(
+        &self,
+        code: &Code<'_, 'strings>,
+        world: &mut World<'strings>,
+        entry: &BlockEntry<'_, 'strings>,
+    ) -> Value<'strings> {
+        match (entry.block.opcode, entry.fields) {
+            (
+                opcode @ (Opcode::argument_reporter_string_number
+                | Opcode::argument_reporter_boolean),
+                Some(Fields::Value(parameter)),
+            ) => self
+                .frames
+                .iter()
+                .rev()
+                .find_map(|frame| frame.arguments.as_ref())
+                .and_then(|arguments| {
+                    arguments
+                        .iter()
+                        .find(|(it, _)| it.to_u32() == parameter.to_u32())
+                })
+                .map_or_else(
+                    || {
+                        if matches!(opcode, Opcode::argument_reporter_boolean) {
+                            Value::Boolean(false)
+                        } else {
+                            Value::Number(0.0)
+                        }
+                    },
+                    |(_, value)| value.clone(),
+                ),
+            (_, Some(&Fields::List(list))) => self.report_list(code, world, entry, list),
+            (Opcode::operator_mathop, Some(Fields::Operator(operator))) => {
+                let n = self.evaluate_input(code, world, entry, "NUM").to_number();
+                Value::Number(fold::math(operator, n))
+            }
+            (Opcode::operator_random, _) => {
+                let from = self.evaluate_input(code, world, entry, "FROM");
+                let to = self.evaluate_input(code, world, entry, "TO");
+                random(world, &from, &to)
+            }
+            (Opcode::sensing_timer, _) => Value::Number(world.clock - world.timer_start),
+            (Opcode::sensing_keypressed, _) => {
+                let key = self.evaluate_input(code, world, entry, "KEY_OPTION");
+                let key = key.to_string().to_lowercase();
+                Value::Boolean(if key == "any" {
+                    !world.keys.is_empty()
+                } else {
+                    world.keys.contains(&key)
+                })
+            }
+            (Opcode::sensing_answer, _) => Value::String("".into()),
+            (opcode, _) => {
+                let arguments = entry
+                    .inputs
+                    .iter()
+                    .map(|(_, input)| self.evaluate(code, world, input))
+                    .collect();
+                // `letter of` can't report lone surrogates.
+                fold::operate(opcode, arguments).unwrap_or_else(|| match opcode {
+                    Opcode::operator_letter_of => Value::String("\u{fffd}".into()),
+                    _ => Value::Number(0.0),
+                })
+            }
+        }
+    }
+
+    fn report_list(
+        &self,
+        code: &Code<'_, 'strings>,
+        world: &mut World<'strings>,
+        entry: &BlockEntry<'_, 'strings>,
+        list: ListRef<'strings>,
+    ) -> Value<'strings> {
+        #[expect(clippy::cast_precision_loss, reason = "lists are never that long")]
+        match entry.block.opcode {
+            Opcode::data_itemoflist => {
+                let index = self.evaluate_input(code, world, entry, "INDEX");
+                let length = world.list(self.instance, list).len();
+                match world.list_index(&index, length, false) {
+                    ListIndex::Item(index) => world.list(self.instance, list)[index].clone(),
+                    ListIndex::All | ListIndex::Invalid => Value::String("".into()),
+                }
+            }
+            Opcode::data_itemnumoflist | Opcode::data_listcontainsitem => {
+                let item = self.evaluate_input(code, world, entry, "ITEM");
+                let position = world
+                    .list(self.instance, list)
+                    .iter()
+                    .position(|it| fold::compare(it, &item) == Ordering::Equal);
+                if matches!(entry.block.opcode, Opcode::data_listcontainsitem) {
+                    Value::Boolean(position.is_some())
+                } else {
+                    Value::Number(position.map_or(0.0, |it| (it + 1) as f64))
+                }
+            }
+            Opcode::data_lengthoflist => {
+                Value::Number(world.list(self.instance, list).len() as f64)
+            }
+            Opcode::data_listcontents => list_contents(world.list(self.instance, list)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn constant<'strings>(constant: &Constant<'strings>) -> Value<'strings> {
+    match constant {
+        Constant::String(s) => Value::String(s.clone()),
+        Constant::Number(n) => Value::Number(*n),
+    }
+}
+
+/// Like the list reporter in the Scratch VM, which joins the items with
+/// spaces unless they are all single letters.
+fn list_contents<'strings>(items: &[Value<'strings>]) -> Value<'strings> {
+    let single_letters = items
+        .iter()
+        .all(|item| matches!(item, Value::String(s) if s.encode_utf16().count() == 1));
+    let separator = if single_letters { "" } else { " " };
+    let items = items.iter().map(ToString::to_string).collect::<Vec<_>>();
+    Value::String(Cow::Owned(items.join(separator)))
+}
+
+/// Like `operator_random` in the Scratch VM.
+#[expect(clippy::suboptimal_flops, reason = "JavaScript doesn't fuse them")]
+fn random<'strings>(world: &mut World, from: &Value, to: &Value) -> Value<'strings> {
+    let is_int = |value: &Value| match value {
+        Value::Number(n) => n.is_nan() || (n.is_finite() && n.fract() == 0.0),
+        Value::String(s) => !s.contains('.'),
+        Value::Boolean(_) => true,
+    };
+    let (from_number, to_number) = (from.to_number(), to.to_number());
+    let (low, high) = if from_number <= to_number {
+        (from_number, to_number)
+    } else {
+        (to_number, from_number)
+    };
+    if low == high {
+        return Value::Number(low);
+    }
+    let random = world.random();
+    Value::Number(if is_int(from) && is_int(to) {
+        low + (random * (high + 1.0 - low)).floor()
+    } else {
+        random * (high - low) + low
+    })
+}
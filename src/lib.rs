@@ -1,20 +1,50 @@
 pub mod block;
+mod cloud;
 mod costume;
-
+mod edit;
+mod fold;
+mod frame;
+mod function;
+mod import;
+#[cfg(feature = "interp")]
+pub mod interp;
+mod monitor;
+mod optimize;
+mod parse;
+mod pretty;
+mod scratchblocks;
+mod sound;
+mod stats;
+mod validate;
+
+pub use cloud::CloudVariableError;
 pub use costume::Costume;
-
-use block::{Block, Fields, Input, Opcode};
-use std::io::{self, Write as _};
+pub use edit::BlockRef;
+pub use frame::{Frame, FramedBlockRef, LocalRef};
+pub use function::FunctionRef;
+pub use monitor::{ListMonitorOptions, MonitorMode, MonitorOptions};
+pub use stats::{Stats, TargetStats};
+
+use block::{Block, Fields, Input, InputName, Opcode};
+use monitor::Monitor;
+use sound::Sound;
+use std::{borrow::Cow, collections::BTreeMap, fs, io, path::Path};
 use tec::Tec;
 
 pub struct Project<'strings> {
     targets: Vec<RealTarget<'strings>>,
+    fold_constants: bool,
+    streaming: bool,
 }
 
 impl Default for Project<'_> {
     fn default() -> Self {
-        let targets = Vec::from([RealTarget::new("Stage", true)]);
-        Self { targets }
+        let targets = Vec::from([RealTarget::new(Cow::Borrowed("Stage"), 0)]);
+        Self {
+            targets,
+            fold_constants: false,
+            streaming: false,
+        }
     }
 }
 
@@ -23,15 +53,84 @@ impl<'strings> Project<'strings> {
         self.target(0)
     }
 
-    pub fn add_sprite(&mut self, name: &'strings str) -> Target<'strings, '_> {
-        self.targets.push(RealTarget::new(name, false));
-        self.target(self.targets.len() - 1)
+    /// Adds an empty sprite with the given name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Project`] already has a sprite with the same name.
+    pub fn add_sprite(&mut self, name: impl Into<Cow<'strings, str>>) -> Target<'strings, '_> {
+        let target = RealTarget::new(name.into(), self.next_target_index());
+        self.push_sprite(target)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Adds a sprite unless the [`Project`] already has one with the same
+    /// name, since Scratch tells sprites apart by their names.
+    fn push_sprite(
+        &mut self,
+        target: RealTarget<'strings>,
+    ) -> Result<Target<'strings, '_>, String> {
+        if self
+            .targets
+            .iter()
+            .any(|it| !it.is_stage && it.name == target.name)
+        {
+            return Err(format!(
+                "project already has a sprite named {:?}",
+                target.name
+            ));
+        }
+        self.targets.push(target);
+        Ok(self.target(self.targets.len() - 1))
+    }
+
+    /// The index that the next target added to the project gets.
+    fn next_target_index(&self) -> u32 {
+        u32::try_from(self.targets.len()).unwrap_or_else(|_| panic!("too many sprites"))
+    }
+
+    /// Enables or disables constant folding, which is disabled by default.
+    ///
+    /// When enabled, operators whose operands are all constants are evaluated
+    /// at build time with the same semantics as Scratch instead of creating a
+    /// block. Since Scratch has no boolean literals, a folded boolean still
+    /// becomes a single block if it is used as an input.
+    pub const fn set_constant_folding(&mut self, enabled: bool) {
+        self.fold_constants = enabled;
+    }
+
+    /// Enables or disables streaming, which is disabled by default.
+    ///
+    /// When enabled, starting a new script serializes all scripts of the
+    /// target that were built so far and frees their blocks, which keeps
+    /// memory use low for huge projects. Every script therefore has to be
+    /// finished before the next one is started: [`InsertionPoint`]s,
+    /// [`Operand`]s and [`BlockRef`]s from earlier scripts, including the
+    /// bodies of custom blocks, can't be used afterwards, and doing so
+    /// panics. Scripts that have been serialized are not touched by
+    /// [`Project::optimize`] or [`Project::simplify`].
+    pub const fn set_streaming(&mut self, enabled: bool) {
+        self.streaming = enabled;
     }
 
     fn target(&mut self, index: usize) -> Target<'strings, '_> {
+        let (stage, sprites) = self
+            .targets
+            .split_first_mut()
+            .unwrap_or_else(|| unreachable!());
+        let (inner, stage) = match index.checked_sub(1) {
+            Some(index) => (&mut sprites[index], Some(&*stage)),
+            None => (stage, None),
+        };
         Target {
-            inner: &mut self.targets[index],
+            inner,
+            stage,
             place: Place::Nowhere,
+            fold_constants: self.fold_constants,
+            streaming: self.streaming,
+            pending_results: 0,
+            enclosing_results: 0,
+            stack_offsets: Vec::new(),
         }
     }
 
@@ -40,62 +139,381 @@ impl<'strings> Project<'strings> {
     ///
     /// # Errors
     ///
-    /// This function will return an error if writing to the `writer` fails.
+    /// This function will return an error if writing to the `writer` fails
+    /// or if a target uses a variable or list of another sprite.
     ///
     /// [`File`]: std::fs::File
     pub fn finish(
         self,
         writer: impl io::Write + io::Seek,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut archive = rawzip::ZipArchiveWriter::new(writer);
+        self.finish_with(writer, &FinishOptions::default())
+    }
 
-        for costume in self.targets.iter().flat_map(|target| &target.costumes) {
-            costume.add_to_archive(&mut archive)?;
+    /// Like [`Project::finish`] but with the given [`FinishOptions`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to the `writer` fails,
+    /// if a target uses a variable or list of another sprite or if the
+    /// `project.json` file would exceed the configured size limit. In debug
+    /// builds, it also returns an error if [`Project::validate`] fails.
+    pub fn finish_with(
+        self,
+        writer: impl io::Write + io::Seek,
+        options: &FinishOptions,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if cfg!(debug_assertions) {
+            self.validate()?;
         }
+        write_archive(writer, self.assets(), "project.json", options, |writer| {
+            self.serialize(writer)
+        })
+    }
 
-        let (mut entry, config) = archive
-            .new_file("project.json")
-            .compression_method(rawzip::CompressionMethod::Deflate)
-            .start()?;
-        let encoder =
-            flate2::write::DeflateEncoder::new(&mut entry, flate2::Compression::default());
-        let mut file = config.wrap(encoder);
+    /// Writes the sprite with the given name as a `.sprite3` file,
+    /// which can be imported into other projects. The options apply to the
+    /// `sprite.json` file like to `project.json`, except for the size limit.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the [`Project`] has no sprite
+    /// with the given name, if the sprite uses a variable or list of another
+    /// sprite or if writing to the `writer` fails.
+    pub fn export_sprite(
+        &self,
+        name: &str,
+        writer: impl io::Write + io::Seek,
+        options: &FinishOptions,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let target = self
+            .targets
+            .iter()
+            .find(|target| !target.is_stage && target.name == name)
+            .ok_or_else(|| format!("project has no sprite named {name:?}"))?;
+        target
+            .validate_owners()
+            .map_err(|err| format!("invalid target {name:?}: {err}"))?;
+        if cfg!(debug_assertions) {
+            target.validate()?;
+        }
+        // The size limit only applies to `project.json`.
+        let options = FinishOptions {
+            max_project_json_size: None,
+            ..*options
+        };
+        write_archive(writer, target.assets(), "sprite.json", &options, |writer| {
+            target.serialize(&self.targets[0], writer)
+        })
+    }
+
+    /// Writes the [`Project`] as an unpacked directory at the given [`Path`],
+    /// creating it if it doesn't exist. The directory has the same contents
+    /// as the ZIP file written by [`Project::finish`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing any of the files fails,
+    /// if a target uses a variable or list of another sprite or if the
+    /// `project.json` file would exceed the configured size limit, in which
+    /// case the directory is left with an incomplete `project.json`.
+    pub fn finish_to_dir(
+        self,
+        path: &Path,
+        options: &FinishOptions,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if cfg!(debug_assertions) {
+            self.validate()?;
+        }
+        fs::create_dir_all(path)?;
+        for asset in self.assets() {
+            fs::write(path.join(asset.file_name), asset.content)?;
+        }
 
-        write!(file, r#"{{"meta":{{"semver":"3.0.0"}},"targets":["#)?;
+        let mut file = io::BufWriter::new(fs::File::create(path.join("project.json"))?);
+        serialize_with(&mut file, options, |writer| self.serialize(writer))?;
+        io::Write::flush(&mut file)?;
+
+        Ok(())
+    }
+
+    /// Writes only the `project.json` file of the [`Project`],
+    /// without any assets or ZIP container.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to the `writer` fails
+    /// or if a target uses a variable or list of another sprite.
+    pub fn write_json(&self, mut writer: impl io::Write) -> io::Result<()> {
+        self.serialize(&mut writer)
+    }
+
+    /// Returns the asset files that [`Project::finish`] would write alongside
+    /// the `project.json` file.
+    pub fn assets(&self) -> impl Iterator<Item = Asset<'_>> {
+        self.targets.iter().flat_map(RealTarget::assets)
+    }
+
+    fn serialize(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+        self.validate_owners().map_err(io::Error::other)?;
+        write!(writer, r#"{{"meta":{{"semver":"3.0.0"}},"targets":["#)?;
+        let stage = &self.targets[0];
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+
+            // Each target is serialized into its own buffer so that the
+            // output is the same as when serializing them one by one.
+            let targets = self
+                .targets
+                .par_iter()
+                .map(|target| {
+                    let mut buffer = Vec::new();
+                    target.serialize(stage, &mut buffer).map(|()| buffer)
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            for (i, target) in targets.iter().enumerate() {
+                if i != 0 {
+                    write!(writer, ",")?;
+                }
+                writer.write_all(target)?;
+            }
+        }
+        #[cfg(not(feature = "rayon"))]
         for (i, target) in self.targets.iter().enumerate() {
             if i != 0 {
-                write!(file, ",")?;
+                write!(writer, ",")?;
             }
-            target.serialize(&mut file)?;
+            target.serialize(stage, writer)?;
         }
-        write!(file, "]}}")?;
+        write!(writer, r#"],"monitors":["#)?;
+        let mut needs_comma = false;
+        for target in &self.targets {
+            target.serialize_monitors(stage, &mut needs_comma, writer)?;
+        }
+        write!(writer, "]}}")
+    }
+}
+
+fn serialize_with(
+    writer: &mut dyn io::Write,
+    options: &FinishOptions,
+    serialize: impl FnOnce(&mut dyn io::Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut limited;
+    let writer: &mut dyn io::Write = if let Some(limit) = options.max_project_json_size {
+        limited = SizeLimit {
+            writer,
+            written: 0,
+            limit,
+        };
+        &mut limited
+    } else {
+        writer
+    };
+    if options.pretty {
+        serialize(&mut pretty::PrettyWriter::new(writer))
+    } else {
+        serialize(writer)
+    }
+}
 
-        let (_, descriptor) = file.finish()?;
-        let _: u64 = entry.finish(descriptor)?;
+/// Fails once more than `limit` bytes have been written, so that the size
+/// limit is checked while writing instead of serializing everything twice.
+struct SizeLimit<'writer> {
+    writer: &'writer mut dyn io::Write,
+    written: u64,
+    limit: u64,
+}
 
-        _ = archive.finish()?;
+impl io::Write for SizeLimit<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written + buf.len() as u64 > self.limit {
+            return Err(io::Error::other(format!(
+                "project.json exceeds the limit of {} bytes",
+                self.limit
+            )));
+        }
+        let written = self.writer.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
 
-        Ok(())
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes a ZIP file containing the assets and a JSON file.
+fn write_archive<'project>(
+    writer: impl io::Write + io::Seek,
+    assets: impl IntoIterator<Item = Asset<'project>>,
+    json_file_name: &str,
+    options: &FinishOptions,
+    serialize: impl FnOnce(&mut dyn io::Write) -> io::Result<()>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut archive = rawzip::ZipArchiveWriter::new(writer);
+
+    // Sorting the assets keeps the archive the same regardless of the order
+    // in which costumes and sounds were added. Targets that share an asset
+    // only need one copy of it.
+    let mut assets = assets.into_iter().collect::<Vec<_>>();
+    assets.sort_unstable_by(|a, b| a.file_name.cmp(&b.file_name));
+    assets.dedup_by(|a, b| a.file_name == b.file_name);
+    for asset in assets {
+        asset.add_to_archive(&mut archive, options)?;
+    }
+
+    add_file(
+        &mut archive,
+        json_file_name,
+        Some(options.compression()),
+        |file| serialize_with(file, options, serialize),
+    )?;
+
+    _ = archive.finish()?;
+
+    Ok(())
+}
+
+/// Every file in the archive gets the same modification time so that building
+/// the same project twice produces byte-identical files.
+fn archive_timestamp() -> rawzip::time::UtcDateTime {
+    // The earliest time that ZIP files can represent.
+    rawzip::time::UtcDateTime::from_components(1980, 1, 1, 0, 0, 0, 0)
+        .unwrap_or_else(|| unreachable!())
+}
+
+/// An asset file of a [`Project`], as returned by [`Project::assets`].
+pub struct Asset<'project> {
+    /// The name of the file, which is its MD5 hash and extension.
+    pub file_name: String,
+    pub content: &'project [u8],
+}
+
+impl Asset<'_> {
+    fn add_to_archive(
+        &self,
+        archive: &mut rawzip::ZipArchiveWriter<impl io::Write>,
+        options: &FinishOptions,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let is_uncompressed = [".svg", ".wav"]
+            .iter()
+            .any(|extension| self.file_name.ends_with(extension));
+        let compression =
+            (options.deflate_uncompressed_assets && is_uncompressed).then(|| options.compression());
+        add_file(archive, &self.file_name, compression, |file| {
+            file.write_all(self.content)
+        })
+    }
+}
+
+/// Adds a file to an archive, deflating it if a compression level is given.
+fn add_file(
+    archive: &mut rawzip::ZipArchiveWriter<impl io::Write>,
+    name: &str,
+    compression: Option<flate2::Compression>,
+    write: impl FnOnce(&mut dyn io::Write) -> io::Result<()>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let method = if compression.is_some() {
+        rawzip::CompressionMethod::Deflate
+    } else {
+        rawzip::CompressionMethod::Store
+    };
+    let (mut entry, config) = archive
+        .new_file(name)
+        .compression_method(method)
+        .last_modified(archive_timestamp())
+        .start()?;
+    let descriptor = if let Some(compression) = compression {
+        let encoder = flate2::write::DeflateEncoder::new(&mut entry, compression);
+        let mut file = config.wrap(encoder);
+        write(&mut file)?;
+        let (encoder, descriptor) = file.finish()?;
+        _ = encoder.finish()?;
+        descriptor
+    } else {
+        let mut file = config.wrap(&mut entry);
+        write(&mut file)?;
+        file.finish()?.1
+    };
+    let _: u64 = entry.finish(descriptor)?;
+    Ok(())
+}
+
+/// Options for [`Project::finish_with`].
+///
+/// The output only depends on the project and these options: keys are always
+/// written in the same order, assets are sorted by name and the archive
+/// contains no timestamps of the build, so it is suitable for reproducible
+/// builds and golden tests.
+#[derive(Default)]
+pub struct FinishOptions {
+    /// The maximum size of the `project.json` file in bytes.
+    /// The Scratch website rejects projects where it exceeds 5 MB.
+    pub max_project_json_size: Option<u64>,
+    /// Whether to indent the `project.json` file to make it easier to read.
+    pub pretty: bool,
+    /// The deflate level from 0 (fastest) to 9 (smallest) used for
+    /// `project.json` and compressed assets. Higher values are clamped to 9.
+    /// Defaults to 6.
+    pub compression_level: Option<u32>,
+    /// Whether to deflate SVG and WAV assets instead of storing them as-is.
+    /// Other asset formats are already compressed.
+    pub deflate_uncompressed_assets: bool,
+}
+
+impl FinishOptions {
+    fn compression(&self) -> flate2::Compression {
+        self.compression_level
+            .map_or_else(flate2::Compression::default, |level| {
+                flate2::Compression::new(level.min(9))
+            })
     }
 }
 
 struct RealTarget<'strings> {
-    name: &'strings str,
+    name: Cow<'strings, str>,
+    /// The position of the target in the project, which is 0 for the stage.
+    index: u32,
     is_stage: bool,
     costumes: Vec<Costume<'strings>>,
+    sounds: Vec<Sound<'strings>>,
     variables: Tec<Variable<'strings>>,
+    /// The variables whose values are stored on the cloud server.
+    cloud_variables: Vec<tec::Id<Variable<'strings>>>,
     lists: Tec<List<'strings>>,
     blocks: Tec<block::Block>,
-    inputs: Tec<(&'static str, Input<'strings>)>,
+    inputs: Tec<(InputName, Input<'strings>)>,
+    /// The inputs of each block, indexed by the ID of the block.
+    input_ranges: Vec<tec::Range<(InputName, Input<'strings>)>>,
     fields: Tec<Fields<'strings>>,
     mutations: Tec<Mutation>,
     parameters: Tec<Parameter>,
     custom_blocks: Tec<CustomBlock>,
+    /// The list that holds the results of functions and local variables.
+    call_stack: Option<tec::Id<List<'strings>>>,
+    monitors: Vec<Monitor<'strings>>,
+    /// Blocks from imported sprites, which are serialized as they are.
+    raw_blocks: Vec<RawBlock>,
     comments: Vec<Comment>,
+    /// Blocks that have already been serialized when streaming,
+    /// each preceded by a comma.
+    streamed_blocks: Vec<u8>,
+    /// The number of streamed blocks with each opcode.
+    streamed_opcodes: BTreeMap<String, usize>,
+    /// The number of blocks that have been streamed.
+    block_id_offset: u32,
 }
 
+// Targets are serialized on several threads with the `rayon` feature.
+const _: () = {
+    const fn assert_sync<T: Sync>() {}
+    assert_sync::<RealTarget<'static>>();
+};
+
 impl RealTarget<'_> {
-    fn serialize(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+    fn serialize(&self, stage: &Self, writer: &mut dyn io::Write) -> io::Result<()> {
         write!(
             writer,
             r#"{{"name":{:?},"isStage":{},"currentCostume":0,"costumes":["#,
@@ -107,31 +525,168 @@ impl RealTarget<'_> {
             }
             costume.serialize(writer)?;
         }
-        write!(writer, r#"],"sounds":[],"variables":{{"#)?;
+        write!(writer, r#"],"sounds":["#)?;
+        for (i, sound) in self.sounds.iter().enumerate() {
+            if i != 0 {
+                write!(writer, ",")?;
+            }
+            sound.serialize(writer)?;
+        }
+        write!(writer, r#"],"variables":{{"#)?;
         for (i, variable) in &self.variables {
             if i.to_u32() != 0 {
                 write!(writer, ",")?;
             }
-            write!(writer, r#""v{}":"#, i.to_u32())?;
-            variable.serialize(writer)?;
+            let id = VariableRef {
+                target: self.index,
+                id: i,
+            };
+            write!(writer, r#""{}":"#, id.serialized_id())?;
+            let is_cloud = self
+                .cloud_variables
+                .iter()
+                .any(|it| it.to_u32() == i.to_u32());
+            variable.serialize(is_cloud, writer)?;
         }
         write!(writer, r#"}},"lists":{{"#)?;
         for (i, list) in &self.lists {
             if i.to_u32() != 0 {
                 write!(writer, ",")?;
             }
-            write!(writer, r#""l{}":"#, i.to_u32())?;
+            let id = ListRef {
+                target: self.index,
+                id: i,
+            };
+            write!(writer, r#""{}":"#, id.serialized_id())?;
             list.serialize(writer)?;
         }
         write!(writer, r#"}},"blocks":{{"#)?;
-        let mut all_inputs = &*self.inputs;
-        let mut fields = self.fields.values().copied();
-        let mut mutations = self.mutations.values().copied();
-        for (i, block) in &self.blocks {
-            if i.to_u32() != 0 {
+        writer.write_all(self.streamed_blocks.strip_prefix(b",").unwrap_or_default())?;
+        let mut needs_comma = !self.streamed_blocks.is_empty();
+        for entry in self.blocks_with_data() {
+            if std::mem::replace(&mut needs_comma, true) {
                 write!(writer, ",")?;
             }
-            write!(writer, "{}:", block::Id(i))?;
+            self.serialize_block(stage, &entry, writer)?;
+        }
+        for (i, block) in self.raw_blocks.iter().enumerate() {
+            if std::mem::replace(&mut needs_comma, true) {
+                write!(writer, ",")?;
+            }
+            write!(writer, r#""i{i}":{}"#, block.json)?;
+        }
+        write!(writer, r#"}},"comments":{{"#)?;
+        for (i, comment) in self.comments.iter().enumerate() {
+            if i != 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, r#""c{i}":"#)?;
+            comment.serialize(writer)?;
+        }
+        write!(writer, "}}}}")
+    }
+}
+
+impl<'strings> RealTarget<'strings> {
+    fn serialize_block(
+        &self,
+        stage: &Self,
+        entry: &BlockEntry,
+        writer: &mut dyn io::Write,
+    ) -> io::Result<()> {
+        write!(writer, "{}:", self.block_id(block::Id(entry.id)))?;
+        entry.block.serialize(
+            entry.inputs,
+            entry.fields,
+            entry.mutation,
+            self,
+            stage,
+            writer,
+        )
+    }
+
+    /// Serializes all blocks in the arenas and frees them. The stage is only
+    /// given to sprites.
+    ///
+    /// Blocks that use a variable or list of another sprite are kept so that
+    /// serializing the project reports the error.
+    fn stream_blocks(&mut self, stage: Option<&Self>) {
+        if self.validate_owners().is_err() {
+            return;
+        }
+        let mut streamed_blocks = std::mem::take(&mut self.streamed_blocks);
+        let mut streamed_opcodes = std::mem::take(&mut self.streamed_opcodes);
+        let stage = stage.unwrap_or(&*self);
+        for entry in self.blocks_with_data() {
+            streamed_blocks.push(b',');
+            self.serialize_block(stage, &entry, &mut streamed_blocks)
+                .unwrap_or_else(|_| unreachable!());
+            *streamed_opcodes
+                .entry(format!("{:?}", entry.block.opcode))
+                .or_default() += 1;
+        }
+        self.streamed_blocks = streamed_blocks;
+        self.streamed_opcodes = streamed_opcodes;
+
+        self.block_id_offset += self.blocks.next_id().to_u32();
+        self.blocks = Tec::new();
+        self.inputs = Tec::new();
+        self.input_ranges.clear();
+        self.fields = Tec::new();
+        self.mutations = Tec::new();
+    }
+
+    /// Panics if a handle was created before the blocks of the target were
+    /// last streamed, which means that the block it refers to is gone and
+    /// its ID may have been reused.
+    fn check_generation(&self, generation: u32, what: &str) {
+        assert!(
+            generation == self.block_id_offset,
+            "{what} was created before its script was streamed and can't be used anymore"
+        );
+    }
+
+    /// Returns the target that a variable or list used by this target belongs
+    /// to, which is either this target or the stage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it belongs to another sprite, which is checked by
+    /// [`RealTarget::validate_owners`] before serializing.
+    fn owner<'a>(&'a self, stage: &'a Self, target: u32) -> &'a Self {
+        if target == self.index {
+            self
+        } else if target == 0 {
+            stage
+        } else {
+            panic!("{:?} uses a variable or list of another sprite", self.name)
+        }
+    }
+
+    /// Whether a block is inside of another one, such as in one of its inputs.
+    fn is_descendant(&self, mut block: block::Id, ancestor: block::Id) -> bool {
+        while block.0.to_u32() != ancestor.0.to_u32() {
+            let Some(parent) = self.blocks[block.0].parent else {
+                return false;
+            };
+            block = parent;
+        }
+        true
+    }
+
+    /// The ID that a block is serialized with, which stays unique after
+    /// earlier blocks have been streamed.
+    const fn block_id(&self, id: block::Id) -> block::Id {
+        block::Id(id.0.strict_add(self.block_id_offset))
+    }
+
+    /// Iterates over the blocks along with their inputs, fields and mutations,
+    /// which are stored in separate arenas in the same order as the blocks.
+    fn blocks_with_data(&self) -> impl Iterator<Item = BlockEntry<'_, 'strings>> {
+        let mut all_inputs = &*self.inputs;
+        let mut fields = self.fields.values();
+        let mut mutations = self.mutations.values().copied();
+        IntoIterator::into_iter(&self.blocks).map(move |(id, block)| {
             let mutation = matches!(
                 block.opcode,
                 Opcode::procedures_call | Opcode::procedures_prototype
@@ -140,8 +695,8 @@ impl RealTarget<'_> {
             let input_count = block.opcode.input_count().unwrap_or_else(|| {
                 self.custom_blocks[mutation.unwrap_or_else(|| unreachable!()).0 .0]
                     .parameters
-                    .len()
-            }) as usize;
+                    .len() as usize
+            });
             let inputs = all_inputs
                 .split_off(..input_count)
                 .unwrap_or_else(|| unreachable!());
@@ -149,39 +704,84 @@ impl RealTarget<'_> {
                 .opcode
                 .has_fields()
                 .then(|| fields.next().unwrap_or_else(|| unreachable!()));
-            block.serialize(inputs, fields, mutation, self, writer)?;
-        }
-        write!(writer, r#"}},"comments":{{"#)?;
-        for (i, comment) in self.comments.iter().enumerate() {
-            if i != 0 {
-                write!(writer, ",")?;
+            BlockEntry {
+                id,
+                block,
+                inputs,
+                fields,
+                mutation,
             }
-            write!(writer, r#""c{i}":"#)?;
-            comment.serialize(writer)?;
-        }
-        write!(writer, "}}}}")
+        })
     }
-}
 
-impl<'strings> RealTarget<'strings> {
-    const fn new(name: &'strings str, is_stage: bool) -> Self {
+    fn assets(&self) -> impl Iterator<Item = Asset<'_>> {
+        let costumes = self.costumes.iter().map(|costume| Asset {
+            file_name: costume.file_name(),
+            content: costume.content(),
+        });
+        let sounds = self.sounds.iter().map(|sound| Asset {
+            file_name: sound.file_name(),
+            content: sound.content(),
+        });
+        costumes.chain(sounds)
+    }
+
+    const fn new(name: Cow<'strings, str>, index: u32) -> Self {
         Self {
             name,
-            is_stage,
+            index,
+            is_stage: index == 0,
             costumes: Vec::new(),
+            sounds: Vec::new(),
             variables: Tec::new(),
+            cloud_variables: Vec::new(),
             lists: Tec::new(),
             blocks: Tec::new(),
             inputs: Tec::new(),
+            input_ranges: Vec::new(),
             fields: Tec::new(),
             mutations: Tec::new(),
             parameters: Tec::new(),
             custom_blocks: Tec::new(),
+            call_stack: None,
+            monitors: Vec::new(),
+            raw_blocks: Vec::new(),
             comments: Vec::new(),
+            streamed_blocks: Vec::new(),
+            streamed_opcodes: BTreeMap::new(),
+            block_id_offset: 0,
         }
     }
 }
 
+struct BlockEntry<'target, 'strings> {
+    id: tec::Id<Block>,
+    block: &'target Block,
+    inputs: &'target [(InputName, Input<'strings>)],
+    fields: Option<&'target Fields<'strings>>,
+    mutation: Option<Mutation>,
+}
+
+impl<'target, 'strings> BlockEntry<'target, 'strings> {
+    /// Returns the input with the given name, which the opcode of the block
+    /// must have.
+    pub(crate) fn input(&self, name: &str) -> &'target Input<'strings> {
+        self.inputs
+            .iter()
+            .find(|(it, _)| matches!(it, InputName::Static(it) if *it == name))
+            .map_or_else(
+                || unreachable!("block has no input named {name:?}"),
+                |(_, input)| input,
+            )
+    }
+}
+
+struct RawBlock {
+    /// Variables and lists placed directly in the code area don't have one.
+    opcode: Option<String>,
+    json: String,
+}
+
 struct Comment {
     text: String,
 }
@@ -198,7 +798,22 @@ impl Comment {
 
 pub struct Target<'strings, 'project> {
     inner: &'project mut RealTarget<'strings>,
+    /// The stage if this is a sprite, whose variables and lists can be used
+    /// by every sprite.
+    stage: Option<&'project RealTarget<'strings>>,
     place: Place<'strings>,
+    fold_constants: bool,
+    streaming: bool,
+    /// The number of results of functions that have been called but not
+    /// used yet, which are at the end of the call stack.
+    pending_results: u32,
+    /// The number of results of functions that were used by the C blocks
+    /// around the place and stay on the call stack until they finish.
+    enclosing_results: u32,
+    /// The blocks that have been created since the last block was placed
+    /// which read from the call stack, along with their inputs that hold
+    /// offsets from the end of it.
+    stack_offsets: Vec<(block::Id, tec::Id<(InputName, Input<'strings>)>)>,
 }
 
 impl<'strings> Target<'strings, '_> {
@@ -210,24 +825,93 @@ impl<'strings> Target<'strings, '_> {
         self.inner.costumes.push(costume);
     }
 
+    /// Adds a variable to the target. Variables of the stage can be used by
+    /// every sprite, while variables of a sprite can only be used by itself.
     pub fn add_variable(&mut self, variable: Variable<'strings>) -> VariableRef<'strings> {
-        VariableRef(self.inner.variables.push(variable))
+        VariableRef {
+            target: self.inner.index,
+            id: self.inner.variables.push(variable),
+        }
     }
 
+    /// Adds a list to the target. Lists of the stage can be used by every
+    /// sprite, while lists of a sprite can only be used by itself.
     pub fn add_list(&mut self, list: List<'strings>) -> ListRef<'strings> {
-        ListRef(self.inner.lists.push(list))
+        ListRef {
+            target: self.inner.index,
+            id: self.inner.lists.push(list),
+        }
+    }
+
+    /// Finds a variable by name, such as one from an imported sprite.
+    /// Like in Scratch, variables of the target take precedence over
+    /// variables of the stage with the same name.
+    #[must_use]
+    pub fn find_variable(&self, name: &str) -> Option<VariableRef<'strings>> {
+        [Some(&*self.inner), self.stage]
+            .into_iter()
+            .flatten()
+            .find_map(|target| {
+                IntoIterator::into_iter(&target.variables)
+                    .find(|(_, variable)| variable.name == name)
+                    .map(|(id, _)| VariableRef {
+                        target: target.index,
+                        id,
+                    })
+            })
+    }
+
+    /// Finds a list by name, such as one from an imported sprite.
+    /// Like in Scratch, lists of the target take precedence over lists of
+    /// the stage with the same name.
+    #[must_use]
+    pub fn find_list(&self, name: &str) -> Option<ListRef<'strings>> {
+        [Some(&*self.inner), self.stage]
+            .into_iter()
+            .flatten()
+            .find_map(|target| {
+                IntoIterator::into_iter(&target.lists)
+                    .find(|(_, list)| list.name == name)
+                    .map(|(id, _)| ListRef {
+                        target: target.index,
+                        id,
+                    })
+            })
     }
 
+    /// # Panics
+    ///
+    /// Panics if the point is in a script that has been streamed.
     #[expect(
         clippy::needless_pass_by_value,
         reason = "`InsertionPoint` is intentionally impossible to copy"
     )]
     pub const fn insert_at(&mut self, point: InsertionPoint<'strings>) -> InsertionPoint<'strings> {
-        self.insert_at_(point.0)
+        assert!(
+            point.1 == self.inner.block_id_offset,
+            "insertion point was created before its script was streamed and can't be used anymore"
+        );
+        self.insert_at_(point.0, point.2)
+    }
+
+    const fn insert_at_(
+        &mut self,
+        place: Place<'strings>,
+        enclosing_results: u32,
+    ) -> InsertionPoint<'strings> {
+        InsertionPoint(
+            std::mem::replace(&mut self.place, place),
+            self.inner.block_id_offset,
+            std::mem::replace(&mut self.enclosing_results, enclosing_results),
+        )
     }
 
-    const fn insert_at_(&mut self, place: Place<'strings>) -> InsertionPoint<'strings> {
-        InsertionPoint(std::mem::replace(&mut self.place, place))
+    /// Continues in a substack of a C block that was just placed. The results
+    /// of functions that the block used stay on the call stack until it
+    /// finishes, so they are below the results and local variables of the
+    /// blocks in the substack.
+    const fn enter(&mut self, place: Place<'strings>, results: u32) -> InsertionPoint<'strings> {
+        self.insert_at_(place, self.enclosing_results + results)
     }
 
     pub fn add_custom_block(
@@ -246,29 +930,19 @@ impl<'strings> Target<'strings, '_> {
             },
         });
 
-        let definition = block::Id(self.inner.blocks.next_id().strict_add(1));
         let _: tec::Id<_> = self.inner.mutations.push(Mutation(CustomBlockRef(index)));
-        let prototype = self.insert(Block {
-            opcode: Opcode::procedures_prototype,
-            parent: Some(definition),
-            next: None,
-        });
+        let prototype = self.insert(Block::new(Opcode::procedures_prototype));
 
         let end = self.inner.parameters.next_id();
         for parameter in (tec::Range { start, end }) {
-            let id = &*parameter.to_u32().to_string().leak();
             let input = self.custom_block_parameter_raw(parameter).0;
-            self.add_inputs(prototype, [(id, input)]);
+            self.add_inputs(prototype, [(InputName::Parameter(parameter), input)]);
         }
 
-        let id = block::Id(self.inner.blocks.push(Block {
-            opcode: Opcode::procedures_definition,
-            parent: None,
-            next: None,
-        }));
-        self.add_inputs(id, [("custom_block", Input::Prototype(prototype))]);
+        let definition = self.insert(Block::new(Opcode::procedures_definition));
+        self.add_inputs(definition, [("custom_block", Input::Prototype(prototype))]);
 
-        let point = InsertionPoint(Place::After(definition));
+        let point = InsertionPoint(Place::After(definition), self.inner.block_id_offset, 0);
         (CustomBlockRef(index), point)
     }
 
@@ -276,6 +950,19 @@ impl<'strings> Target<'strings, '_> {
     ///
     /// Panics if no script has been started.
     pub fn use_custom_block(&mut self, block: CustomBlockRef, arguments: Vec<Operand<'strings>>) {
+        let _: block::Id = self.call_custom_block(block, arguments);
+        self.release_results();
+    }
+
+    fn call_custom_block(
+        &mut self,
+        block: CustomBlockRef,
+        arguments: Vec<Operand<'strings>>,
+    ) -> block::Id {
+        let arguments = arguments
+            .into_iter()
+            .map(|arg| self.materialize(arg.0))
+            .collect::<Vec<_>>();
         let _: tec::Id<_> = self.inner.mutations.push(Mutation(block));
         let (Place::After(parent) | Place::Inside { block: parent, .. }) = self.place else {
             panic!("cannot put block when no script has been started");
@@ -290,11 +977,12 @@ impl<'strings> Target<'strings, '_> {
             self.inner.custom_blocks[block.0]
                 .parameters
                 .into_iter()
-                .map(|it| &*it.to_u32().to_string().leak())
-                .zip(arguments.into_iter().map(|arg| arg.0)),
+                .map(InputName::Parameter)
+                .zip(arguments),
         );
         self.set_next(id);
         self.place = Place::After(id);
+        id
     }
 
     /// # Panics
@@ -321,20 +1009,44 @@ impl<'strings> Target<'strings, '_> {
     }
 
     pub fn start_script(&mut self, hat: block::Hat<'strings>) {
+        self.pending_results = 0;
+        self.enclosing_results = 0;
+        self.stack_offsets.clear();
+        if self.streaming {
+            self.inner.stream_blocks(self.stage);
+        }
         self.inner.fields.extend(hat.fields);
-        let id = block::Id(self.inner.blocks.push(Block {
+        let id = self.insert(Block {
             opcode: hat.opcode,
             parent: None,
             next: None,
-        }));
+        });
         self.place = Place::After(id);
     }
 
-    pub fn put(&mut self, block: block::Stacking<'strings>) {
-        let _: block::Id = self.put_(block);
+    pub fn put(&mut self, block: block::Stacking<'strings>) -> BlockRef {
+        BlockRef::new(self.put_(block), self.inner.block_id_offset)
     }
 
     fn put_(&mut self, block: block::Stacking<'strings>) -> block::Id {
+        // Blocks after this one would never run, but it has no inputs either,
+        // so results can be removed before it instead.
+        if block.ends_script() {
+            self.release_results();
+            return self.put_without_release(block);
+        }
+        let id = self.put_without_release(block);
+        self.release_results();
+        id
+    }
+
+    /// Puts a block without removing the results of functions that it uses
+    /// from the list of return values.
+    fn put_without_release(&mut self, block: block::Stacking<'strings>) -> block::Id {
+        let inputs = Vec::from(block.inputs)
+            .into_iter()
+            .map(|(name, input)| (name, self.materialize(input)))
+            .collect::<Vec<_>>();
         self.inner.fields.extend(block.fields);
         let (Place::After(parent) | Place::Inside { block: parent, .. }) = self.place else {
             panic!("cannot put block when no script has been started");
@@ -344,7 +1056,7 @@ impl<'strings> Target<'strings, '_> {
             parent: Some(parent),
             next: None,
         });
-        self.add_inputs(id, block.inputs);
+        self.add_inputs(id, inputs);
         self.set_next(id);
         self.place = Place::After(id);
         id
@@ -362,12 +1074,13 @@ impl<'strings> Target<'strings, '_> {
 
     pub fn repeat(&mut self, times: Operand<'strings>) -> InsertionPoint<'strings> {
         let input = self.inner.inputs.next_id();
+        let results = self.pending_results;
         let block = self.put_(block::Stacking {
             opcode: Opcode::control_repeat,
             inputs: Box::new([("SUBSTACK", Input::EmptySubstack), ("TIMES", times.0)]),
             fields: None,
         });
-        self.insert_at_(Place::Inside { block, input })
+        self.enter(Place::Inside { block, input }, results)
     }
 
     pub fn for_(
@@ -376,16 +1089,18 @@ impl<'strings> Target<'strings, '_> {
         times: Operand<'strings>,
     ) -> InsertionPoint<'strings> {
         let input = self.inner.inputs.next_id();
+        let results = self.pending_results;
         let block = self.put_(block::Stacking {
             opcode: Opcode::control_for_each,
             inputs: Box::new([("SUBSTACK", Input::EmptySubstack), ("VALUE", times.0)]),
             fields: Some(Fields::Variable(variable)),
         });
-        self.insert_at_(Place::Inside { block, input })
+        self.enter(Place::Inside { block, input }, results)
     }
 
     pub fn if_(&mut self, condition: Operand<'strings>) -> InsertionPoint<'strings> {
         let input = self.inner.inputs.next_id();
+        let results = self.pending_results;
         let block = self.put_(block::Stacking {
             opcode: Opcode::control_if,
             inputs: Box::new([
@@ -394,11 +1109,12 @@ impl<'strings> Target<'strings, '_> {
             ]),
             fields: None,
         });
-        self.insert_at_(Place::Inside { block, input })
+        self.enter(Place::Inside { block, input }, results)
     }
 
     pub fn if_else(&mut self, condition: Operand<'strings>) -> [InsertionPoint<'strings>; 2] {
         let input = self.inner.inputs.next_id();
+        let results = self.pending_results;
         let block = self.put_(block::Stacking {
             opcode: Opcode::control_if_else,
             inputs: Box::new([
@@ -408,15 +1124,24 @@ impl<'strings> Target<'strings, '_> {
             ]),
             fields: None,
         });
-        let after = self.insert_at_(Place::Inside { block, input });
-        let else_ = InsertionPoint(Place::Inside {
-            block,
-            input: input.strict_add(1),
-        });
+        let after = self.enter(Place::Inside { block, input }, results);
+        let else_ = InsertionPoint(
+            Place::Inside {
+                block,
+                input: input.strict_add(1),
+            },
+            self.inner.block_id_offset,
+            self.enclosing_results,
+        );
         [after, else_]
     }
 
+    /// # Panics
+    ///
+    /// Panics if no script has been started or if the condition uses the
+    /// result of [`Target::call_function`], which would only be computed once.
     pub fn while_(&mut self, condition: Operand<'strings>) -> InsertionPoint<'strings> {
+        self.check_loop_condition();
         let input = self.inner.inputs.next_id();
         let block = self.put_(block::Stacking {
             opcode: Opcode::control_while,
@@ -426,10 +1151,15 @@ impl<'strings> Target<'strings, '_> {
             ]),
             fields: None,
         });
-        self.insert_at_(Place::Inside { block, input })
+        self.enter(Place::Inside { block, input }, 0)
     }
 
+    /// # Panics
+    ///
+    /// Panics if no script has been started or if the condition uses the
+    /// result of [`Target::call_function`], which would only be computed once.
     pub fn repeat_until(&mut self, condition: Operand<'strings>) -> InsertionPoint<'strings> {
+        self.check_loop_condition();
         let input = self.inner.inputs.next_id();
         let block = self.put_(block::Stacking {
             opcode: Opcode::control_repeat_until,
@@ -439,7 +1169,7 @@ impl<'strings> Target<'strings, '_> {
             ]),
             fields: None,
         });
-        self.insert_at_(Place::Inside { block, input })
+        self.enter(Place::Inside { block, input }, 0)
     }
 
     pub fn add(&mut self, lhs: Operand<'strings>, rhs: Operand<'strings>) -> Operand<'strings> {
@@ -526,10 +1256,10 @@ impl<'strings> Target<'strings, '_> {
     pub fn item_of_list(
         &mut self,
         list: ListRef<'strings>,
-        index: Operand<'strings>,
+        index: block::ListIndex<'strings>,
     ) -> Operand<'strings> {
         let _: tec::Id<_> = self.inner.fields.push(Fields::List(list));
-        self.op(Opcode::data_itemoflist, [("INDEX", index.0)])
+        self.op(Opcode::data_itemoflist, [("INDEX", index.into_input())])
     }
 
     pub fn item_num_of_list(
@@ -550,6 +1280,13 @@ impl<'strings> Target<'strings, '_> {
         self.op(Opcode::data_lengthoflist, [])
     }
 
+    /// Reports the items of a list joined into a string like
+    /// `Operand::from(list)` does, but as a block.
+    pub fn list_contents(&mut self, list: ListRef<'strings>) -> Operand<'strings> {
+        let _: tec::Id<_> = self.inner.fields.push(Fields::List(list));
+        self.op(Opcode::data_listcontents, [])
+    }
+
     pub fn letter_of(
         &mut self,
         string: Operand<'strings>,
@@ -589,6 +1326,11 @@ impl<'strings> Target<'strings, '_> {
     }
 
     pub fn mathop(&mut self, operator: &'static str, num: Operand<'strings>) -> Operand<'strings> {
+        if self.fold_constants {
+            if let Some(result) = fold::mathop(operator, &num.0) {
+                return Operand(result);
+            }
+        }
         let _: tec::Id<_> = self.inner.fields.push(Fields::Operator(operator));
         self.op(Opcode::operator_mathop, [("NUM", num.0)])
     }
@@ -612,35 +1354,65 @@ impl<'strings> Target<'strings, '_> {
     pub fn clone_self(&mut self) {
         let _: tec::Id<_> = self.inner.fields.push(Fields::CloneSelf);
         let menu = self.insert(Block::new(Opcode::control_create_clone_of_menu));
-        self.put(block::Stacking {
+        let _: block::Id = self.put_(block::Stacking {
             opcode: Opcode::control_create_clone_of,
             inputs: Box::new([("CLONE_OPTION", Input::Prototype(menu))]),
             fields: None,
         });
     }
 
-    fn op(
+    fn op<const N: usize>(
         &mut self,
         opcode: Opcode,
-        inputs: impl IntoIterator<Item = (&'static str, Input<'strings>)>,
+        inputs: [(&'static str, Input<'strings>); N],
     ) -> Operand<'strings> {
+        if self.fold_constants {
+            if let Some(result) = fold::evaluate(opcode, &inputs) {
+                return Operand(result);
+            }
+        }
+        let inputs = inputs.map(|(name, input)| (name, self.materialize(input)));
         let id = self.insert(Block {
             opcode,
             parent: None,
             next: None,
         });
         self.add_inputs(id, inputs);
-        Operand(Input::Substack(id))
+        Operand(Input::Reporter(id, self.inner.block_id_offset))
+    }
+
+    /// Replaces a folded boolean with an operator block that evaluates to it,
+    /// relying on empty boolean inputs being treated as false.
+    fn materialize(&mut self, input: Input<'strings>) -> Input<'strings> {
+        let Input::Boolean(value) = input else {
+            return input;
+        };
+        let operand = if value {
+            self.op(Opcode::operator_not, [("OPERAND", Input::EmptySubstack)])
+        } else {
+            self.op(
+                Opcode::operator_and,
+                [
+                    ("OPERAND1", Input::EmptySubstack),
+                    ("OPERAND2", Input::EmptySubstack),
+                ],
+            )
+        };
+        operand.0
     }
 
     fn insert(&mut self, block: Block) -> block::Id {
+        let start = self.inner.inputs.next_id();
+        self.inner
+            .input_ranges
+            .push(tec::Range { start, end: start });
         block::Id(self.inner.blocks.push(block))
     }
 
     fn set_next(&mut self, next: block::Id) {
         match self.place {
             Place::Nowhere => {}
-            Place::After(block) => self.inner.blocks[block.0].parent = next.into(),
+            Place::After(block) => self.inner.blocks[block.0].next = next.into(),
             Place::Inside { input, .. } => self.inner.inputs[input].1 = Input::Substack(next),
         }
     }
@@ -648,17 +1420,50 @@ impl<'strings> Target<'strings, '_> {
     fn add_inputs(
         &mut self,
         parent: block::Id,
-        inputs: impl IntoIterator<Item = (&'static str, Input<'strings>)>,
+        inputs: impl IntoIterator<Item = (impl Into<InputName>, Input<'strings>)>,
     ) {
-        self.inner.inputs.extend(inputs.into_iter().inspect(|it| {
-            if let Input::Substack(it) = it.1 {
-                self.inner.blocks[it.0].parent = Some(parent);
-            }
-        }));
+        self.inner
+            .inputs
+            .extend(inputs.into_iter().map(|(name, input)| {
+                let input = attach(input, self.inner.block_id_offset);
+                if let Input::Substack(it) | Input::Prototype(it) = input {
+                    self.inner.blocks[it.0].parent = Some(parent);
+                }
+                (name.into(), input)
+            }));
+        // The inputs of a block are added right after it, except for those
+        // of a prototype, which come after the parameter reporters that have
+        // no inputs themselves.
+        self.inner.input_ranges[parent.0.to_u32() as usize].end = self.inner.inputs.next_id();
     }
 }
 
-pub struct InsertionPoint<'strings>(Place<'strings>);
+/// A place where [`Target::put`] puts blocks, as returned by
+/// [`Target::insert_at`] and the methods that add blocks containing others.
+///
+/// The second field is the number of blocks that had been streamed when the
+/// point was created, like for [`BlockRef`]. The third is the number of
+/// results of functions that are on the call stack while blocks at the point
+/// run because the C blocks around it use them.
+pub struct InsertionPoint<'strings>(Place<'strings>, u32, u32);
+
+/// Turns a reporter held by an [`Operand`] into an input of a block.
+///
+/// # Panics
+///
+/// Panics if the reporter has been streamed since the operand was created.
+fn attach(input: Input<'_>, block_id_offset: u32) -> Input<'_> {
+    match input {
+        Input::Reporter(block, generation) => {
+            assert!(
+                generation == block_id_offset,
+                "operand was created before its script was streamed and can't be used anymore"
+            );
+            Input::Substack(block)
+        }
+        input => input,
+    }
+}
 
 #[derive(Clone, Copy)]
 enum Place<'strings> {
@@ -666,12 +1471,12 @@ enum Place<'strings> {
     After(block::Id),
     Inside {
         block: block::Id,
-        input: tec::Id<(&'static str, Input<'strings>)>,
+        input: tec::Id<(InputName, Input<'strings>)>,
     },
 }
 
 pub enum Constant<'strings> {
-    String(&'strings str),
+    String(Cow<'strings, str>),
     Number(f64),
 }
 
@@ -679,6 +1484,11 @@ impl Constant<'_> {
     fn serialize(&self, writer: &mut dyn io::Write) -> io::Result<()> {
         match self {
             Self::String(s) => write!(writer, "{s:?}"),
+            // JSON has no representation for these numbers.
+            Self::Number(n) if n.is_nan() => write!(writer, r#""NaN""#),
+            Self::Number(n) if n.is_infinite() => {
+                write!(writer, r#""{}Infinity""#, if *n < 0.0 { "-" } else { "" })
+            }
             Self::Number(n) => write!(writer, "{n}"),
         }
     }
@@ -690,15 +1500,31 @@ pub struct Variable<'strings> {
 }
 
 impl Variable<'_> {
-    fn serialize(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+    fn serialize(&self, is_cloud: bool, writer: &mut dyn io::Write) -> io::Result<()> {
         write!(writer, "[{:?},", self.name)?;
         self.value.serialize(writer)?;
+        if is_cloud {
+            write!(writer, ",true")?;
+        }
         write!(writer, "]")
     }
 }
 
 #[derive(Clone, Copy)]
-pub struct VariableRef<'strings>(tec::Id<Variable<'strings>>);
+pub struct VariableRef<'strings> {
+    /// The index of the target that has the variable.
+    target: u32,
+    id: tec::Id<Variable<'strings>>,
+}
+
+impl VariableRef<'_> {
+    /// The ID of the variable in `project.json`, which is unique in the
+    /// whole project because Scratch uses it to find the variable of a
+    /// monitor.
+    fn serialized_id(self) -> String {
+        format!("v{}_{}", self.target, self.id.to_u32())
+    }
+}
 
 pub struct List<'strings> {
     pub name: String,
@@ -707,7 +1533,7 @@ pub struct List<'strings> {
 
 impl List<'_> {
     fn serialize(&self, writer: &mut dyn io::Write) -> io::Result<()> {
-        write!(writer, "[{:?}, [", self.name)?;
+        write!(writer, "[{:?},[", self.name)?;
         for (i, item) in self.items.iter().enumerate() {
             if i != 0 {
                 write!(writer, ",")?;
@@ -719,7 +1545,19 @@ impl List<'_> {
 }
 
 #[derive(Clone, Copy)]
-pub struct ListRef<'strings>(tec::Id<List<'strings>>);
+pub struct ListRef<'strings> {
+    /// The index of the target that has the list.
+    target: u32,
+    id: tec::Id<List<'strings>>,
+}
+
+impl ListRef<'_> {
+    /// The ID of the list in `project.json`, which is unique in the whole
+    /// project like [`VariableRef::serialized_id`].
+    fn serialized_id(self) -> String {
+        format!("l{}_{}", self.target, self.id.to_u32())
+    }
+}
 
 #[derive(Clone)]
 pub struct Parameter {
@@ -752,48 +1590,34 @@ impl Mutation {
         writer: &mut dyn io::Write,
     ) -> io::Result<()> {
         let block = &target.custom_blocks[self.0 .0];
+        let parameters = &target.parameters[block.parameters];
+        // Scratch stores these lists as JSON inside of strings.
+        let mut proccode = block.name.clone();
+        let mut ids = Vec::new();
+        let mut names = Vec::new();
+        let mut defaults = Vec::new();
+        for (id, parameter) in block.parameters.into_iter().zip(parameters) {
+            let (placeholder, default) = match parameter.kind {
+                ParameterKind::StringOrNumber => (" %s", ""),
+                ParameterKind::Boolean => (" %b", "false"),
+            };
+            proccode.push_str(placeholder);
+            ids.push(format!(r#""{}""#, id.to_u32()));
+            names.push(format!("{:?}", parameter.name));
+            defaults.push(format!("{default:?}"));
+        }
         write!(
             writer,
-            r#"{{"tagName":"mutation","children":[],"proccode":"{}"#,
-            block.name.escape_debug()
+            r#"{{"tagName":"mutation","children":[],"proccode":{proccode:?},"argumentids":{:?},"warp":"true""#,
+            format!("[{}]", ids.join(",")),
         )?;
-        for parameter in &target.parameters[block.parameters] {
-            writer.write_all(match parameter.kind {
-                ParameterKind::StringOrNumber => b" %s",
-                ParameterKind::Boolean => b" %b",
-            })?;
-        }
-        write!(writer, r#"","argumentids":""#)?;
-        for (index, parameter) in block.parameters.into_iter().enumerate() {
-            if index != 0 {
-                write!(writer, ",")?;
-            }
-            write!(writer, r#"\"{}\""#, parameter.to_u32())?;
-        }
-        write!(writer, r#"","warp":true"#)?;
         if is_prototype {
-            write!(writer, r#","argumentnames":"["#)?;
-            for (index, parameter) in target.parameters[block.parameters].iter().enumerate() {
-                if index != 0 {
-                    write!(writer, ",")?;
-                }
-                write!(writer, r#"""#)?;
-                for c in parameter.name.escape_debug().flat_map(char::escape_debug) {
-                    write!(writer, "{c}")?;
-                }
-                write!(writer, r#"""#)?;
-            }
-            write!(writer, r#"]","argumentdefaults":""#)?;
-            for (index, parameter) in target.parameters[block.parameters].iter().enumerate() {
-                if index != 0 {
-                    write!(writer, ",")?;
-                }
-                writer.write_all(match parameter.kind {
-                    ParameterKind::StringOrNumber => br#""""#,
-                    ParameterKind::Boolean => b"false",
-                })?;
-            }
-            write!(writer, r#"""#)?;
+            write!(
+                writer,
+                r#","argumentnames":{:?},"argumentdefaults":{:?}"#,
+                format!("[{}]", names.join(",")),
+                format!("[{}]", defaults.join(",")),
+            )?;
         }
         write!(writer, "}}")
     }
@@ -809,6 +1633,18 @@ impl From<f64> for Operand<'_> {
 
 impl<'strings> From<&'strings str> for Operand<'strings> {
     fn from(value: &'strings str) -> Self {
+        Self(Input::String(Cow::Borrowed(value)))
+    }
+}
+
+impl From<String> for Operand<'_> {
+    fn from(value: String) -> Self {
+        Self(Input::String(Cow::Owned(value)))
+    }
+}
+
+impl<'strings> From<Cow<'strings, str>> for Operand<'strings> {
+    fn from(value: Cow<'strings, str>) -> Self {
         Self(Input::String(value))
     }
 }
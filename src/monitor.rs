@@ -0,0 +1,175 @@
+use crate::{ListRef, RealTarget, Target, VariableRef};
+use std::io;
+
+/// How a variable monitor is displayed.
+#[derive(Clone, Copy, Default)]
+pub enum MonitorMode {
+    /// The name of the variable next to its value.
+    #[default]
+    Default,
+    /// Only the value in a bigger font.
+    Large,
+    /// Like [`MonitorMode::Default`] with a slider that sets the value.
+    Slider,
+}
+
+/// Options for [`Target::show_variable_monitor`].
+///
+/// Positions are in pixels from the top left corner of the stage.
+pub struct MonitorOptions {
+    pub mode: MonitorMode,
+    pub x: f64,
+    pub y: f64,
+    /// The smallest value of the slider.
+    pub min: f64,
+    /// The largest value of the slider. The slider only allows whole numbers
+    /// if both ends of its range are whole numbers.
+    pub max: f64,
+    /// Whether the monitor is shown when the project is loaded.
+    pub visible: bool,
+}
+
+impl Default for MonitorOptions {
+    fn default() -> Self {
+        Self {
+            mode: MonitorMode::Default,
+            x: 0.0,
+            y: 0.0,
+            min: 0.0,
+            max: 100.0,
+            visible: true,
+        }
+    }
+}
+
+/// Options for [`Target::show_list_monitor`].
+///
+/// Positions and sizes are in pixels. A size of 0 lets Scratch pick one.
+pub struct ListMonitorOptions {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// Whether the monitor is shown when the project is loaded.
+    pub visible: bool,
+}
+
+impl Default for ListMonitorOptions {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+            visible: true,
+        }
+    }
+}
+
+pub enum Monitor<'strings> {
+    Variable(VariableRef<'strings>, MonitorOptions),
+    List(ListRef<'strings>, ListMonitorOptions),
+}
+
+impl<'strings> Target<'strings, '_> {
+    /// Adds a monitor to the stage that displays the value of a variable.
+    /// Its visibility can be changed while the project runs with
+    /// [`block::show_variable`] and [`block::hide_variable`].
+    ///
+    /// [`block::show_variable`]: crate::block::show_variable
+    /// [`block::hide_variable`]: crate::block::hide_variable
+    pub fn show_variable_monitor(
+        &mut self,
+        variable: VariableRef<'strings>,
+        options: MonitorOptions,
+    ) {
+        self.inner
+            .monitors
+            .push(Monitor::Variable(variable, options));
+    }
+
+    /// Adds a monitor to the stage that displays the items of a list.
+    /// Its visibility can be changed while the project runs with
+    /// [`block::show_list`] and [`block::hide_list`].
+    ///
+    /// [`block::show_list`]: crate::block::show_list
+    /// [`block::hide_list`]: crate::block::hide_list
+    pub fn show_list_monitor(&mut self, list: ListRef<'strings>, options: ListMonitorOptions) {
+        self.inner.monitors.push(Monitor::List(list, options));
+    }
+}
+
+impl RealTarget<'_> {
+    /// Serializes the monitors of the target as elements of the `monitors`
+    /// array of the project.
+    ///
+    /// Scratch uses the ID of a monitor as the ID of its variable, so it is
+    /// the ID that the variable is serialized with.
+    pub(crate) fn serialize_monitors(
+        &self,
+        stage: &Self,
+        needs_comma: &mut bool,
+        writer: &mut dyn io::Write,
+    ) -> io::Result<()> {
+        let sprite_name = |owner: &Self| {
+            if owner.is_stage {
+                "null".to_owned()
+            } else {
+                format!("{:?}", owner.name)
+            }
+        };
+        for monitor in &self.monitors {
+            if std::mem::replace(needs_comma, true) {
+                write!(writer, ",")?;
+            }
+            match *monitor {
+                Monitor::Variable(variable, ref options) => {
+                    let id = variable.serialized_id();
+                    let owner = self.owner(stage, variable.target);
+                    let sprite_name = sprite_name(owner);
+                    let variable = &owner.variables[variable.id];
+                    let name = &variable.name;
+                    let mode = match options.mode {
+                        MonitorMode::Default => "default",
+                        MonitorMode::Large => "large",
+                        MonitorMode::Slider => "slider",
+                    };
+                    write!(
+                        writer,
+                        r#"{{"id":{id:?},"mode":"{mode}","opcode":"data_variable","params":{{"VARIABLE":{name:?}}},"spriteName":{sprite_name},"value":"#,
+                    )?;
+                    variable.value.serialize(writer)?;
+                    let is_discrete = options.min.fract() == 0.0 && options.max.fract() == 0.0;
+                    write!(
+                        writer,
+                        r#","width":0,"height":0,"x":{},"y":{},"visible":{},"sliderMin":{},"sliderMax":{},"isDiscrete":{is_discrete}}}"#,
+                        options.x, options.y, options.visible, options.min, options.max,
+                    )?;
+                }
+                Monitor::List(list, ref options) => {
+                    let id = list.serialized_id();
+                    let owner = self.owner(stage, list.target);
+                    let sprite_name = sprite_name(owner);
+                    let list = &owner.lists[list.id];
+                    write!(
+                        writer,
+                        r#"{{"id":{id:?},"mode":"list","opcode":"data_listcontents","params":{{"LIST":{:?}}},"spriteName":{sprite_name},"value":["#,
+                        list.name
+                    )?;
+                    for (i, item) in list.items.iter().enumerate() {
+                        if i != 0 {
+                            write!(writer, ",")?;
+                        }
+                        item.serialize(writer)?;
+                    }
+                    write!(
+                        writer,
+                        r#"],"width":{},"height":{},"x":{},"y":{},"visible":{}}}"#,
+                        options.width, options.height, options.x, options.y, options.visible,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
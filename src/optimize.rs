@@ -0,0 +1,416 @@
+use crate::{
+    block::{self, Block, Fields, Input, Opcode},
+    BlockEntry, Project, RealTarget,
+};
+use std::collections::HashSet;
+use tec::Tec;
+
+impl Project<'_> {
+    /// Removes blocks that can never run and renumbers the remaining ones
+    /// compactly. This includes:
+    /// - blocks following `stop all`, `stop this script` or `forever`,
+    /// - definitions of custom blocks that are never used,
+    /// - scripts under `when I receive` hats for messages that are never
+    ///   broadcast,
+    /// - reporters that were created but never used as an input.
+    ///
+    /// Messages broadcast with a non-constant operand are assumed to be able
+    /// to trigger any receiver.
+    ///
+    /// Block IDs change, so [`InsertionPoint`]s obtained before calling this
+    /// must not be used afterwards.
+    ///
+    /// [`InsertionPoint`]: crate::InsertionPoint
+    pub fn optimize(&mut self) {
+        let live = find_live_blocks(&self.targets);
+        for (target, live) in self.targets.iter_mut().zip(live) {
+            target.retain_blocks(&live, Input::clone);
+        }
+    }
+
+    /// Rewrites operators that have no effect into their operands.
+    /// Since Scratch converts values implicitly, most of these rules only apply
+    /// when the operand is known to be of the right type, such as the result of
+    /// a reporter that always returns a number. The rules are:
+    /// - `x + 0`, `0 + x` and `0 - (0 - x)` become `x` if `x` is a number that
+    ///   is neither NaN nor negative zero,
+    /// - `x - 0`, `x * 1`, `1 * x` and `x / 1` become `x` if `x` is a number
+    ///   that is not NaN,
+    /// - `not (not x)` becomes `x` if `x` is a boolean,
+    /// - `join "" x` and `join x ""` become `x` if `x` is a string.
+    ///
+    /// Block IDs change, so [`InsertionPoint`]s obtained before calling this
+    /// must not be used afterwards.
+    ///
+    /// [`InsertionPoint`]: crate::InsertionPoint
+    pub fn simplify(&mut self) {
+        for target in &mut self.targets {
+            target.simplify();
+        }
+    }
+}
+
+/// The messages that can be broadcast, lowercased since Scratch matches
+/// them case-insensitively.
+#[derive(Default)]
+struct Broadcasts {
+    messages: HashSet<String>,
+    any: bool,
+}
+
+fn find_live_blocks(targets: &[RealTarget]) -> Vec<Vec<bool>> {
+    let entries = targets
+        .iter()
+        .map(|target| target.blocks_with_data().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut live = entries
+        .iter()
+        .map(|entries| vec![false; entries.len()])
+        .collect::<Vec<_>>();
+    // Streamed blocks are not analyzed, so assume that they use every custom
+    // block and can broadcast any message. The same goes for imported
+    // broadcasts.
+    let mut used_custom_blocks = targets
+        .iter()
+        .map(|target| {
+            if target.streamed_blocks.is_empty() {
+                HashSet::new()
+            } else {
+                IntoIterator::into_iter(&target.custom_blocks)
+                    .map(|(id, _)| id.to_u32())
+                    .collect()
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut broadcasts = Broadcasts {
+        any: targets.iter().any(|target| {
+            !target.streamed_blocks.is_empty()
+                || target.raw_blocks.iter().any(|block| {
+                    matches!(
+                        block.opcode.as_deref(),
+                        Some("event_broadcast" | "event_broadcastandwait")
+                    )
+                })
+        }),
+        ..Broadcasts::default()
+    };
+
+    // Broadcasts cross target boundaries, so keep marking until nothing
+    // changes in any target.
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for ((entries, live), used_custom_blocks) in
+            entries.iter().zip(&mut live).zip(&mut used_custom_blocks)
+        {
+            let mut stack = entries
+                .iter()
+                .enumerate()
+                .filter(|&(i, entry)| {
+                    !live[i] && is_root(entry, entries, used_custom_blocks, &broadcasts)
+                })
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            while let Some(i) = stack.pop() {
+                if std::mem::replace(&mut live[i], true) {
+                    continue;
+                }
+                changed = true;
+                let entry = &entries[i];
+                match entry.block.opcode {
+                    Opcode::procedures_call => {
+                        let mutation = entry.mutation.unwrap_or_else(|| unreachable!());
+                        _ = used_custom_blocks.insert(mutation.0 .0.to_u32());
+                    }
+                    Opcode::event_broadcastandwait => match &entry.inputs[0].1 {
+                        Input::String(message) => {
+                            _ = broadcasts.messages.insert(message.to_lowercase());
+                        }
+                        _ => broadcasts.any = true,
+                    },
+                    _ => {}
+                }
+                if !is_terminal(entry) {
+                    stack.extend(entry.block.next.map(index));
+                }
+                stack.extend(entry.inputs.iter().filter_map(|(_, input)| match *input {
+                    Input::Substack(id) | Input::Prototype(id) => Some(index(id)),
+                    _ => None,
+                }));
+            }
+        }
+    }
+
+    live
+}
+
+fn is_root(
+    entry: &BlockEntry,
+    entries: &[BlockEntry],
+    used_custom_blocks: &HashSet<u32>,
+    broadcasts: &Broadcasts,
+) -> bool {
+    match entry.block.opcode {
+        Opcode::event_whenflagclicked
+        | Opcode::event_whenkeypressed
+        | Opcode::control_start_as_clone => true,
+        Opcode::event_whenbroadcastreceived => {
+            let Some(Fields::BroadcastOption(message)) = entry.fields else {
+                unreachable!()
+            };
+            broadcasts.any || broadcasts.messages.contains(&message.to_lowercase())
+        }
+        Opcode::procedures_definition => {
+            let Input::Prototype(prototype) = entry.inputs[0].1 else {
+                unreachable!()
+            };
+            let mutation = entries[index(prototype)]
+                .mutation
+                .unwrap_or_else(|| unreachable!());
+            used_custom_blocks.contains(&mutation.0 .0.to_u32())
+        }
+        _ => false,
+    }
+}
+
+const fn is_terminal(entry: &BlockEntry) -> bool {
+    matches!(entry.block.opcode, Opcode::control_forever)
+        || matches!(entry.fields, Some(Fields::StopAll | Fields::StopThisScript))
+}
+
+fn resolve<'strings>(
+    input: &Input<'strings>,
+    replacements: &[Option<Input<'strings>>],
+) -> Input<'strings> {
+    match input {
+        Input::Substack(id) => replacements
+            .get(index(*id))
+            .cloned()
+            .flatten()
+            .unwrap_or_else(|| input.clone()),
+        _ => input.clone(),
+    }
+}
+
+const fn index(id: block::Id) -> usize {
+    id.0.to_u32() as usize
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    /// A number that is neither NaN nor negative zero.
+    PlainNumber,
+    /// A number that is not NaN.
+    Number,
+    Boolean,
+    String,
+    Unknown,
+}
+
+impl Kind {
+    fn of(input: &Input, entries: &[BlockEntry]) -> Self {
+        match input {
+            Input::Number(n) if n.is_nan() => Self::Unknown,
+            Input::Number(n) if *n == 0.0 && n.is_sign_negative() => Self::Number,
+            Input::Number(_) => Self::PlainNumber,
+            Input::String(_) => Self::String,
+            Input::Substack(id) => match entries[index(*id)].block.opcode {
+                Opcode::data_itemnumoflist
+                | Opcode::data_lengthoflist
+                | Opcode::operator_length
+                | Opcode::sensing_timer => Self::PlainNumber,
+                Opcode::motion_xposition
+                | Opcode::motion_yposition
+                | Opcode::sensing_mousex
+                | Opcode::sensing_mousey => Self::Number,
+                Opcode::data_listcontainsitem
+                | Opcode::operator_and
+                | Opcode::operator_contains
+                | Opcode::operator_equals
+                | Opcode::operator_gt
+                | Opcode::operator_lt
+                | Opcode::operator_not
+                | Opcode::operator_or
+                | Opcode::sensing_keypressed => Self::Boolean,
+                Opcode::operator_join | Opcode::operator_letter_of | Opcode::sensing_answer => {
+                    Self::String
+                }
+                _ => Self::Unknown,
+            },
+            _ => Self::Unknown,
+        }
+    }
+
+    const fn is_number(self) -> bool {
+        matches!(self, Self::PlainNumber | Self::Number)
+    }
+}
+
+#[expect(clippy::float_cmp, reason = "only exact constants are simplified")]
+fn is_number(input: &Input, value: f64) -> bool {
+    matches!(input, Input::Number(n) if *n == value)
+}
+
+fn is_empty_string(input: &Input) -> bool {
+    matches!(input, Input::String(s) if s.is_empty())
+}
+
+/// Returns the input that `entry` can be replaced with, if any, along with an
+/// operand of `entry` that becomes unused because of the replacement.
+/// The inputs of `entry` must already have been resolved.
+fn simplify_entry<'strings>(
+    entry: &BlockEntry<'_, 'strings>,
+    entries: &[BlockEntry<'_, 'strings>],
+    resolve: impl Fn(&Input<'strings>) -> Input<'strings>,
+) -> Option<(Input<'strings>, Option<block::Id>)> {
+    let operand = |i: usize| resolve(&entry.inputs[i].1);
+    let kind = |input: &Input| Kind::of(input, entries);
+
+    match entry.block.opcode {
+        Opcode::operator_add => {
+            let (lhs, rhs) = (operand(0), operand(1));
+            if is_number(&rhs, 0.0) && matches!(kind(&lhs), Kind::PlainNumber) {
+                Some((lhs, None))
+            } else if is_number(&lhs, 0.0) && matches!(kind(&rhs), Kind::PlainNumber) {
+                Some((rhs, None))
+            } else {
+                None
+            }
+        }
+        Opcode::operator_subtract => {
+            let (lhs, rhs) = (operand(0), operand(1));
+            if is_number(&rhs, 0.0) && kind(&lhs).is_number() {
+                return Some((lhs, None));
+            }
+            let Input::Substack(inner_id) = rhs else {
+                return None;
+            };
+            let inner = &entries[index(inner_id)];
+            if !is_number(&lhs, 0.0) || !matches!(inner.block.opcode, Opcode::operator_subtract) {
+                return None;
+            }
+            let (inner_lhs, inner_rhs) = (resolve(&inner.inputs[0].1), resolve(&inner.inputs[1].1));
+            (is_number(&inner_lhs, 0.0) && matches!(kind(&inner_rhs), Kind::PlainNumber))
+                .then_some((inner_rhs, Some(inner_id)))
+        }
+        Opcode::operator_multiply => {
+            let (lhs, rhs) = (operand(0), operand(1));
+            if is_number(&rhs, 1.0) && kind(&lhs).is_number() {
+                Some((lhs, None))
+            } else if is_number(&lhs, 1.0) && kind(&rhs).is_number() {
+                Some((rhs, None))
+            } else {
+                None
+            }
+        }
+        Opcode::operator_divide => {
+            let (lhs, rhs) = (operand(0), operand(1));
+            (is_number(&rhs, 1.0) && kind(&lhs).is_number()).then_some((lhs, None))
+        }
+        Opcode::operator_not => {
+            let Input::Substack(inner_id) = operand(0) else {
+                return None;
+            };
+            let inner = &entries[index(inner_id)];
+            if !matches!(inner.block.opcode, Opcode::operator_not) {
+                return None;
+            }
+            let inner_operand = resolve(&inner.inputs[0].1);
+            matches!(kind(&inner_operand), Kind::Boolean).then_some((inner_operand, Some(inner_id)))
+        }
+        Opcode::operator_join => {
+            let (lhs, rhs) = (operand(0), operand(1));
+            if is_empty_string(&lhs) && matches!(kind(&rhs), Kind::String) {
+                Some((rhs, None))
+            } else if is_empty_string(&rhs) && matches!(kind(&lhs), Kind::String) {
+                Some((lhs, None))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+impl<'strings> RealTarget<'strings> {
+    fn simplify(&mut self) {
+        let entries = self.blocks_with_data().collect::<Vec<_>>();
+        let mut replacements = Vec::<Option<Input>>::with_capacity(entries.len());
+        let mut live = vec![true; entries.len()];
+        for (i, entry) in entries.iter().enumerate() {
+            // Operands are always created before the blocks that use them,
+            // so they have already been simplified.
+            let resolve = |input: &Input<'strings>| resolve(input, &replacements);
+            let replacement = simplify_entry(entry, &entries, resolve).map(|(input, unused)| {
+                live[i] = false;
+                if let Some(unused) = unused {
+                    live[index(unused)] = false;
+                }
+                input
+            });
+            replacements.push(replacement);
+        }
+        drop(entries);
+
+        self.retain_blocks(&live, |input| resolve(input, &replacements));
+    }
+
+    fn retain_blocks(
+        &mut self,
+        live: &[bool],
+        mut replace: impl FnMut(&Input<'strings>) -> Input<'strings>,
+    ) {
+        let mut blocks = Tec::new();
+        let mut inputs = Tec::new();
+        let mut input_ranges = Vec::new();
+        let mut fields = Tec::new();
+        let mut mutations = Tec::new();
+
+        let new_ids = IntoIterator::into_iter(&self.blocks)
+            .map(|(id, block)| {
+                live[index(block::Id(id))].then(|| block::Id(blocks.push(Block::new(block.opcode))))
+            })
+            .collect::<Vec<_>>();
+        let remap = |id: block::Id| new_ids[index(id)];
+
+        let mut reparented = Vec::new();
+        for entry in self.blocks_with_data() {
+            let Some(new_id) = remap(block::Id(entry.id)) else {
+                continue;
+            };
+            let block = &mut blocks[new_id.0];
+            block.parent = entry.block.parent.and_then(remap);
+            block.next = entry.block.next.and_then(remap);
+            let start = inputs.next_id();
+            inputs.extend(entry.inputs.iter().map(|(name, input)| {
+                let input = match replace(input) {
+                    Input::Substack(id) => {
+                        let id = remap(id).unwrap_or_else(|| unreachable!());
+                        reparented.push((id, new_id));
+                        Input::Substack(id)
+                    }
+                    Input::Prototype(id) => {
+                        Input::Prototype(remap(id).unwrap_or_else(|| unreachable!()))
+                    }
+                    other => other,
+                };
+                (*name, input)
+            }));
+            input_ranges.push(tec::Range {
+                start,
+                end: inputs.next_id(),
+            });
+            fields.extend(entry.fields.cloned());
+            mutations.extend(entry.mutation);
+        }
+        for (child, parent) in reparented {
+            blocks[child.0].parent = Some(parent);
+        }
+
+        self.blocks = blocks;
+        self.inputs = inputs;
+        self.input_ranges = input_ranges;
+        self.fields = fields;
+        self.mutations = mutations;
+    }
+}
@@ -0,0 +1,732 @@
+use crate::{
+    block::{self, DeleteIndex, Input, ListIndex},
+    BlockRef, Constant, CustomBlockRef, InsertionPoint, List, ListRef, Operand, Parameter,
+    ParameterKind, Target, Variable, VariableRef,
+};
+use std::{collections::HashSet, error::Error, iter::Peekable, str::Chars};
+
+const MATH_OPERATORS: [&str; 14] = [
+    "abs", "floor", "ceiling", "sqrt", "sin", "cos", "tan", "asin", "acos", "atan", "ln", "log",
+    "e ^", "10 ^",
+];
+
+impl Target<'_, '_> {
+    /// Adds the scripts written in [scratchblocks] syntax, the format that
+    /// [`Project::to_scratchblocks`] produces, to the target.
+    ///
+    /// Each script starts with a hat block or a `define` line and C blocks
+    /// are closed with `end`. Variables and lists that don't exist yet are
+    /// created. Custom blocks can be used before they are defined, as long as
+    /// they are defined in the same text or have already been added to the
+    /// target. The indices `(last)`, `(random)` and `(all)` of list blocks
+    /// name positions rather than variables. Lines starting with `//` are
+    /// ignored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error with the line number if the text
+    /// contains a block that the builder doesn't support or isn't valid
+    /// scratchblocks. The scripts before that line have already been added,
+    /// along with the custom blocks that they call. Custom blocks that are
+    /// defined after that line and not called before it are not added.
+    ///
+    /// [scratchblocks]: https://en.scratch-wiki.info/wiki/Block_Plugin/Syntax
+    /// [`Project::to_scratchblocks`]: crate::Project::to_scratchblocks
+    pub fn add_scratchblocks(&mut self, source: &str) -> Result<(), Box<dyn Error>> {
+        let lines = source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with("//"))
+            .map(|(number, line)| {
+                let mut chars = line.chars().peekable();
+                parse_parts(&mut chars, None)
+                    .map(|parts| (number, parts))
+                    .map_err(|err| format!("line {number}: {err}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let has_else = find_if_elses(&lines);
+        let mut definitions = Vec::new();
+        for (number, parts) in &lines {
+            if let Some(("define", parts)) = split_keyword(parts) {
+                let definition =
+                    parse_definition(parts).map_err(|err| format!("line {number}: {err}"))?;
+                definitions.push(definition);
+            }
+        }
+
+        let mut parser = Parser {
+            target: self,
+            definitions,
+            next_definition: 0,
+            parameters: None,
+            frames: Vec::new(),
+            in_script: false,
+            after_forever: false,
+        };
+        for (i, (number, parts)) in lines.iter().enumerate() {
+            parser
+                .line(parts, has_else.contains(&i))
+                .map_err(|err| format!("line {number}: {err}"))?;
+        }
+        if parser.frames.is_empty() {
+            Ok(())
+        } else {
+            Err("missing `end` at the end of the text".into())
+        }
+    }
+}
+
+/// Parses the name and parameters of a custom block from a `define` line.
+fn parse_definition<'strings>(parts: &[Part]) -> Result<Definition<'strings>, String> {
+    let mut name = Vec::new();
+    let mut parameters = Vec::new();
+    for part in parts {
+        match part {
+            Part::Word(word) if parameters.is_empty() => name.push(word.as_str()),
+            Part::Round(parts) => parameters.push(Parameter {
+                name: words(parts).ok_or("invalid parameter")?,
+                kind: ParameterKind::StringOrNumber,
+            }),
+            Part::Angle(parts) => parameters.push(Parameter {
+                name: words(parts).ok_or("invalid parameter")?,
+                kind: ParameterKind::Boolean,
+            }),
+            _ => return Err("custom blocks can only have parameters after their name".into()),
+        }
+    }
+    if name.is_empty() {
+        return Err("custom block has no name".into());
+    }
+    Ok(Definition {
+        name: name.join(" "),
+        parameters,
+        added: None,
+    })
+}
+
+/// A word or input of a block.
+enum Part {
+    Word(String),
+    /// A number, variable or reporter in round brackets.
+    Round(Vec<Self>),
+    /// A boolean in angle brackets.
+    Angle(Vec<Self>),
+    String(String),
+    Dropdown(String),
+}
+
+fn parse_parts(chars: &mut Peekable<Chars>, close: Option<char>) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut word = String::new();
+    // The opening `<` counts as the previous character so that `<>` is an
+    // empty boolean rather than the start of a greater than operator.
+    let mut previous = if close == Some('>') { '<' } else { ' ' };
+    let flush = |word: &mut String, parts: &mut Vec<Part>| {
+        if !word.is_empty() {
+            parts.push(Part::Word(std::mem::take(word)));
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => word.push(chars.next().ok_or("dangling backslash")?),
+            c if c.is_whitespace() => flush(&mut word, &mut parts),
+            ')' if close == Some(')') => {
+                flush(&mut word, &mut parts);
+                return Ok(parts);
+            }
+            // A `>` with spaces around it is the greater than operator.
+            '>' if close == Some('>')
+                && !(previous.is_whitespace()
+                    && chars.peek().is_some_and(|c| c.is_whitespace())) =>
+            {
+                flush(&mut word, &mut parts);
+                return Ok(parts);
+            }
+            '(' => {
+                flush(&mut word, &mut parts);
+                parts.push(Part::Round(parse_parts(chars, Some(')'))?));
+            }
+            // A `<` followed by a space is the less than operator.
+            '<' if chars.peek().is_some_and(|c| !c.is_whitespace()) => {
+                flush(&mut word, &mut parts);
+                parts.push(Part::Angle(parse_parts(chars, Some('>'))?));
+            }
+            '[' => {
+                flush(&mut word, &mut parts);
+                let mut text = String::new();
+                loop {
+                    match chars.next().ok_or("missing `]`")? {
+                        '\\' => text.push(chars.next().ok_or("dangling backslash")?),
+                        ']' => break,
+                        c => text.push(c),
+                    }
+                }
+                let part = text
+                    .strip_suffix(" v")
+                    .map(|option| Part::Dropdown(option.to_owned()));
+                parts.push(part.unwrap_or(Part::String(text)));
+            }
+            c => word.push(c),
+        }
+        previous = c;
+    }
+    if let Some(close) = close {
+        return Err(format!("missing `{close}`"));
+    }
+    flush(&mut word, &mut parts);
+    Ok(parts)
+}
+
+/// Finds the `if` blocks that have an `else` branch,
+/// since the builder needs to know that up front.
+fn find_if_elses(lines: &[(usize, Vec<Part>)]) -> HashSet<usize> {
+    let mut has_else = HashSet::new();
+    let mut open = Vec::new();
+    for (i, (_, parts)) in lines.iter().enumerate() {
+        let (parts, _) = split_annotation(parts);
+        match signature(parts).as_str() {
+            "forever" | "repeat _" | "repeat until _" | "while _" | "for each _ in _"
+            | "if _ then" => open.push(i),
+            "else" => {
+                if let Some(&i) = open.last() {
+                    _ = has_else.insert(i);
+                }
+            }
+            "end" => _ = open.pop(),
+            _ => {}
+        }
+    }
+    has_else
+}
+
+/// Returns the first word and the rest of the parts.
+fn split_keyword(parts: &[Part]) -> Option<(&str, &[Part])> {
+    match parts.split_first()? {
+        (Part::Word(word), rest) => Some((word, rest)),
+        _ => None,
+    }
+}
+
+/// Splits off the `:: category` that scratchblocks uses to mark blocks
+/// that are not in the Scratch palette.
+fn split_annotation(parts: &[Part]) -> (&[Part], Option<String>) {
+    parts
+        .iter()
+        .position(|part| matches!(part, Part::Word(word) if word == "::"))
+        .map_or((parts, None), |i| (&parts[..i], words(&parts[i + 1..])))
+}
+
+/// The words of a block with `_` in place of each input,
+/// such as `set _ to _`.
+fn signature(parts: &[Part]) -> String {
+    parts
+        .iter()
+        .map(|part| match part {
+            Part::Word(word) => word.as_str(),
+            _ => "_",
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Joins the parts with spaces if they are all words.
+fn words(parts: &[Part]) -> Option<String> {
+    parts
+        .iter()
+        .map(|part| match part {
+            Part::Word(word) => Some(word.as_str()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .filter(|words| !words.is_empty())
+        .map(|words| words.join(" "))
+}
+
+fn inputs(parts: &[Part]) -> Vec<&Part> {
+    parts
+        .iter()
+        .filter(|part| !matches!(part, Part::Word(_)))
+        .collect()
+}
+
+/// The text of a dropdown menu, which may also be written as a string.
+fn option(part: &Part) -> Result<&str, String> {
+    match part {
+        Part::Dropdown(option) | Part::String(option) => Ok(option),
+        _ => Err("expected a dropdown menu".into()),
+    }
+}
+
+/// The text of an index like `(last)`, `(last v)` or `[last]` that may name
+/// a position in a list.
+fn position_name(part: &Part) -> Option<String> {
+    let name = match part {
+        Part::Round(parts) => words(parts)?,
+        Part::String(text) | Part::Dropdown(text) => text.clone(),
+        Part::Word(_) | Part::Angle(_) => return None,
+    };
+    Some(name.strip_suffix(" v").unwrap_or(&name).to_owned())
+}
+
+fn number(text: &str) -> Option<f64> {
+    let is_number = text.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.')
+        || matches!(text, "Infinity" | "NaN");
+    is_number.then(|| text.parse().ok()).flatten()
+}
+
+struct Definition<'strings> {
+    name: String,
+    parameters: Vec<Parameter>,
+    /// The custom block and its body once it has been added to the target,
+    /// which happens at its `define` line or at the first call before it.
+    added: Option<(CustomBlockRef, Option<InsertionPoint<'strings>>)>,
+}
+
+enum Frame<'strings> {
+    Forever,
+    Block {
+        after: InsertionPoint<'strings>,
+        else_: Option<InsertionPoint<'strings>>,
+    },
+}
+
+struct Parser<'target, 'strings, 'project> {
+    target: &'target mut Target<'strings, 'project>,
+    /// The custom blocks of the `define` lines in the text.
+    definitions: Vec<Definition<'strings>>,
+    /// The index of the definition of the next `define` line.
+    next_definition: usize,
+    /// The parameters of the custom block that is being defined.
+    parameters: Option<(CustomBlockRef, Vec<(String, ParameterKind)>)>,
+    /// The C blocks that have not been closed yet.
+    frames: Vec<Frame<'strings>>,
+    in_script: bool,
+    /// Whether the last block was a `forever` loop, which nothing can follow.
+    after_forever: bool,
+}
+
+impl<'strings> Parser<'_, 'strings, '_> {
+    fn line(&mut self, parts: &[Part], has_else: bool) -> Result<(), String> {
+        let (parts, _) = split_annotation(parts);
+        if let Some(("define", _)) = split_keyword(parts) {
+            self.check_closed()?;
+            self.define();
+            return Ok(());
+        }
+
+        let signature = signature(parts);
+        let args = inputs(parts);
+        let hat = match signature.as_str() {
+            "when flag clicked" => Some(block::when_flag_clicked()),
+            "when _ key pressed" => Some(block::when_key_pressed(option(args[0])?.to_owned())),
+            "when I receive _" => Some(block::when_received(option(args[0])?.to_owned())),
+            "when I start as a clone" => Some(block::when_cloned()),
+            _ => None,
+        };
+        if let Some(hat) = hat {
+            self.check_closed()?;
+            self.target.start_script(hat);
+            self.parameters = None;
+            self.in_script = true;
+            self.after_forever = false;
+            return Ok(());
+        }
+
+        match signature.as_str() {
+            "else" => {
+                let Some(Frame::Block { else_, .. }) = self.frames.last_mut() else {
+                    return Err("`else` without `if`".into());
+                };
+                let else_ = else_.take().ok_or("`else` without `if`")?;
+                let _: InsertionPoint = self.target.insert_at(else_);
+                self.after_forever = false;
+                return Ok(());
+            }
+            "end" => {
+                match self.frames.pop().ok_or("`end` without C block")? {
+                    Frame::Forever => self.after_forever = true,
+                    Frame::Block { after, .. } => {
+                        let _: InsertionPoint = self.target.insert_at(after);
+                        self.after_forever = false;
+                    }
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        if !self.in_script {
+            return Err("block is not in a script".into());
+        }
+        if self.after_forever {
+            return Err("blocks cannot follow a `forever` loop".into());
+        }
+        match signature.as_str() {
+            "forever" => {
+                self.target.forever();
+                self.frames.push(Frame::Forever);
+            }
+            "repeat _" | "repeat until _" | "while _" | "for each _ in _" | "if _ then" => {
+                let operand = self.operand(args[args.len() - 1])?;
+                let (after, else_) = match signature.as_str() {
+                    "repeat _" => (self.target.repeat(operand), None),
+                    "repeat until _" => (self.target.repeat_until(operand), None),
+                    "while _" => (self.target.while_(operand), None),
+                    "for each _ in _" => {
+                        let variable = self.variable(args[0])?;
+                        (self.target.for_(variable, operand), None)
+                    }
+                    _ if has_else => {
+                        let [after, else_] = self.target.if_else(operand);
+                        (after, Some(else_))
+                    }
+                    _ => (self.target.if_(operand), None),
+                };
+                self.frames.push(Frame::Block { after, else_ });
+            }
+            "create clone of _" => match option(args[0])? {
+                "myself" => self.target.clone_self(),
+                _ => return Err("only clones of the sprite itself can be created".into()),
+            },
+            _ => {
+                if let Some(block) = self.statement(&signature, &args)? {
+                    let _: BlockRef = self.target.put(block);
+                } else {
+                    self.call(parts)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that all C blocks have been closed before a new script starts.
+    fn check_closed(&self) -> Result<(), String> {
+        if self.frames.is_empty() {
+            Ok(())
+        } else {
+            Err("missing `end` before the next script".into())
+        }
+    }
+
+    /// Converts a stack block other than a C block or a custom block.
+    fn statement(
+        &mut self,
+        signature: &str,
+        args: &[&Part],
+    ) -> Result<Option<block::Stacking<'strings>>, String> {
+        let mut operand = |i: usize| self.operand(args[i]);
+        Ok(Some(match signature {
+            "wait _ seconds" => block::wait(operand(0)?),
+            "stop _" => match option(args[0])? {
+                "all" => block::stop_all(),
+                "this script" => block::stop_this_script(),
+                option => return Err(format!("cannot stop {option:?}")),
+            },
+            "set _ to _" => block::set_variable(self.variable(args[0])?, self.operand(args[1])?),
+            "change _ by _" => {
+                block::change_variable(self.variable(args[0])?, self.operand(args[1])?)
+            }
+            "add _ to _" => block::append(self.list(args[1])?, self.operand(args[0])?),
+            "delete all of _" => block::delete_all_of_list(self.list(args[0])?),
+            "delete _ of _" => {
+                let list = self.list(args[1])?;
+                block::delete_of_list(list, self.delete_index(args[0])?)
+            }
+            "show variable _" => block::show_variable(self.variable(args[0])?),
+            "hide variable _" => block::hide_variable(self.variable(args[0])?),
+            "show list _" => block::show_list(self.list(args[0])?),
+            "hide list _" => block::hide_list(self.list(args[0])?),
+            "insert _ at _ of _" => {
+                let list = self.list(args[2])?;
+                block::insert_at_list(list, self.operand(args[0])?, self.list_index(args[1])?)
+            }
+            "replace item _ of _ with _" => {
+                let list = self.list(args[1])?;
+                block::replace(list, self.list_index(args[0])?, self.operand(args[2])?)
+            }
+            "broadcast _ and wait" => block::broadcast_and_wait(operand(0)?),
+            "go to _ layer" => match option(args[0])? {
+                "front" => block::go_to_front_layer(),
+                "back" => block::go_to_back_layer(),
+                option => return Err(format!("there is no {option:?} layer")),
+            },
+            "hide" => block::hide(),
+            "show" => block::show(),
+            "say _" => block::say(operand(0)?),
+            "say _ for _ seconds" => {
+                let message = operand(0)?;
+                block::say_for_seconds(operand(1)?, message)
+            }
+            "set size to _ %" => block::set_size(operand(0)?),
+            "switch costume to _" => block::set_costume(operand(0)?),
+            "change x by _" => block::change_x(operand(0)?),
+            "change y by _" => block::change_y(operand(0)?),
+            "go to x: _ y: _" => {
+                let x = operand(0)?;
+                block::go_to_xy(x, operand(1)?)
+            }
+            "move _ steps" => block::move_steps(operand(0)?),
+            "set x to _" => block::set_x(operand(0)?),
+            "set y to _" => block::set_y(operand(0)?),
+            "erase all" => block::erase_all(),
+            "pen down" => block::pen_down(),
+            "pen up" => block::pen_up(),
+            "set pen color to _" => block::set_pen_color(operand(0)?),
+            "set pen size to _" => block::set_pen_size(operand(0)?),
+            "stamp" => block::stamp(),
+            "ask _ and wait" => block::ask(operand(0)?),
+            "reset timer" => block::reset_timer(),
+            _ => return Ok(None),
+        }))
+    }
+
+    fn call(&mut self, parts: &[Part]) -> Result<(), String> {
+        let name = parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Word(word) => Some(word.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let args = inputs(parts);
+        let block = IntoIterator::into_iter(&self.target.inner.custom_blocks)
+            .find(|(_, block)| block.name == name && block.parameters.len() as usize == args.len())
+            .map(|(id, _)| CustomBlockRef(id));
+        // Custom blocks that are defined later are added when they are first
+        // called.
+        let block = block
+            .or_else(|| {
+                let index = self.definitions.iter().position(|definition| {
+                    definition.name == name && definition.parameters.len() == args.len()
+                })?;
+                Some(self.add_definition(index))
+            })
+            .ok_or_else(|| format!("unknown block {:?}", signature(parts)))?;
+        let arguments = args
+            .into_iter()
+            .map(|arg| self.operand(arg))
+            .collect::<Result<_, _>>()?;
+        self.target.use_custom_block(block, arguments);
+        Ok(())
+    }
+
+    /// Starts the body of the custom block of the next `define` line.
+    fn define(&mut self) {
+        let index = self.next_definition;
+        self.next_definition += 1;
+        let block = self.add_definition(index);
+        let definition = &mut self.definitions[index];
+        let body = definition
+            .added
+            .as_mut()
+            .and_then(|(_, body)| body.take())
+            .unwrap_or_else(|| unreachable!());
+        let parameters = definition
+            .parameters
+            .iter()
+            .map(|parameter| (parameter.name.clone(), parameter.kind))
+            .collect();
+        let _: InsertionPoint = self.target.insert_at(body);
+        self.parameters = Some((block, parameters));
+        self.in_script = true;
+        self.after_forever = false;
+    }
+
+    /// Adds the custom block of a definition to the target unless it already
+    /// has been.
+    fn add_definition(&mut self, index: usize) -> CustomBlockRef {
+        let definition = &mut self.definitions[index];
+        if let Some((block, _)) = definition.added {
+            return block;
+        }
+        let (block, body) = self.target.add_custom_block(
+            definition.name.clone(),
+            definition.parameters.clone().into_iter(),
+        );
+        definition.added = Some((block, Some(body)));
+        block
+    }
+
+    fn operand(&mut self, part: &Part) -> Result<Operand<'strings>, String> {
+        match part {
+            Part::Word(_) => unreachable!(),
+            Part::String(text) | Part::Dropdown(text) => Ok(text.clone().into()),
+            Part::Round(parts) => self.reporter(parts, ParameterKind::StringOrNumber),
+            Part::Angle(parts) if parts.is_empty() => Ok(Operand(Input::EmptySubstack)),
+            Part::Angle(parts) => self.reporter(parts, ParameterKind::Boolean),
+        }
+    }
+
+    fn reporter(
+        &mut self,
+        parts: &[Part],
+        kind: ParameterKind,
+    ) -> Result<Operand<'strings>, String> {
+        let (parts, annotation) = split_annotation(parts);
+        if parts.is_empty() {
+            return Ok(String::new().into());
+        }
+        if let Some(text) = words(parts) {
+            return self.simple_reporter(text, annotation.as_deref(), kind);
+        }
+
+        let args = inputs(parts);
+        match signature(parts).as_str() {
+            "_ + _" | "_ - _" | "_ * _" | "_ / _" | "_ mod _" | "_ < _" | "_ = _" | "_ > _"
+            | "_ and _" | "_ or _" | "join _ _" | "letter _ of _" | "pick random _ to _" => {
+                let lhs = self.operand(args[0])?;
+                let rhs = self.operand(args[1])?;
+                let target = &mut *self.target;
+                Ok(match signature(parts).as_str() {
+                    "_ + _" => target.add(lhs, rhs),
+                    "_ - _" => target.sub(lhs, rhs),
+                    "_ * _" => target.mul(lhs, rhs),
+                    "_ / _" => target.div(lhs, rhs),
+                    "_ mod _" => target.modulo(lhs, rhs),
+                    "_ < _" => target.lt(lhs, rhs),
+                    "_ = _" => target.eq(lhs, rhs),
+                    "_ > _" => target.gt(lhs, rhs),
+                    "_ and _" => target.and(lhs, rhs),
+                    "_ or _" => target.or(lhs, rhs),
+                    "join _ _" => target.join(lhs, rhs),
+                    "letter _ of _" => target.letter_of(rhs, lhs),
+                    _ => target.random(lhs, rhs),
+                })
+            }
+            "not _" => {
+                let operand = self.operand(args[0])?;
+                Ok(self.target.not(operand))
+            }
+            "length of _" => match args[0] {
+                Part::Dropdown(_) => {
+                    let list = self.list(args[0])?;
+                    Ok(self.target.length_of_list(list))
+                }
+                string => {
+                    let string = self.operand(string)?;
+                    Ok(self.target.length(string))
+                }
+            },
+            "_ contains _ ?" => match args[0] {
+                Part::Dropdown(_) => {
+                    let list = self.list(args[0])?;
+                    let item = self.operand(args[1])?;
+                    Ok(self.target.list_contains_item(list, item))
+                }
+                haystack => {
+                    let haystack = self.operand(haystack)?;
+                    let needle = self.operand(args[1])?;
+                    Ok(self.target.contains(haystack, needle))
+                }
+            },
+            "_ of _" => {
+                let operator = option(args[0])?;
+                let operator = MATH_OPERATORS
+                    .into_iter()
+                    .find(|it| *it == operator)
+                    .ok_or_else(|| format!("unknown math operator {operator:?}"))?;
+                let num = self.operand(args[1])?;
+                Ok(self.target.mathop(operator, num))
+            }
+            "item _ of _" => {
+                let list = self.list(args[1])?;
+                let index = self.list_index(args[0])?;
+                Ok(self.target.item_of_list(list, index))
+            }
+            "item # of _ in _" => {
+                let list = self.list(args[1])?;
+                let item = self.operand(args[0])?;
+                Ok(self.target.item_num_of_list(list, item))
+            }
+            "key _ pressed?" => {
+                let key = self.operand(args[0])?;
+                Ok(self.target.key_is_pressed(key))
+            }
+            signature => Err(format!("unknown reporter {signature:?}")),
+        }
+    }
+
+    /// Converts a reporter without inputs, which is a number, a parameter of
+    /// the custom block being defined, a variable or a list.
+    fn simple_reporter(
+        &mut self,
+        text: String,
+        annotation: Option<&str>,
+        kind: ParameterKind,
+    ) -> Result<Operand<'strings>, String> {
+        if annotation == Some("list") {
+            return self.list(&Part::Dropdown(text)).map(Operand::from);
+        }
+        let is_boolean = matches!(kind, ParameterKind::Boolean);
+        if !is_boolean {
+            if let Some(n) = number(&text) {
+                return Ok(n.into());
+            }
+        }
+        if let Some((block, parameters)) = &self.parameters {
+            if let Some(index) = parameters.iter().position(|(name, parameter_kind)| {
+                *name == text && matches!(parameter_kind, ParameterKind::Boolean) == is_boolean
+            }) {
+                let block = *block;
+                return Ok(self.target.custom_block_parameter(block, index));
+            }
+        }
+        let target = &mut *self.target;
+        Ok(match text.as_str() {
+            "x position" => target.x_position(),
+            "y position" => target.y_position(),
+            "timer" => target.timer(),
+            "answer" => target.answer(),
+            "mouse x" => target.mouse_x(),
+            "mouse y" => target.mouse_y(),
+            _ if is_boolean => return Err(format!("unknown boolean {text:?}")),
+            _ => self.variable(&Part::Dropdown(text))?.into(),
+        })
+    }
+
+    /// Converts the index of a list block, where `(last)` and `(random)` name
+    /// positions instead of variables.
+    fn list_index(&mut self, part: &Part) -> Result<ListIndex<'strings>, String> {
+        Ok(match position_name(part).as_deref() {
+            Some("last") => ListIndex::Last,
+            Some("random" | "any") => ListIndex::Random,
+            _ => ListIndex::At(self.operand(part)?),
+        })
+    }
+
+    /// Converts the index of a `delete _ of _` block, which can also be
+    /// `(all)`.
+    fn delete_index(&mut self, part: &Part) -> Result<DeleteIndex<'strings>, String> {
+        Ok(match position_name(part).as_deref() {
+            Some("last") => DeleteIndex::Last,
+            Some("random" | "any") => DeleteIndex::Random,
+            Some("all") => DeleteIndex::All,
+            _ => DeleteIndex::At(self.operand(part)?),
+        })
+    }
+
+    /// Finds or creates the variable that a dropdown menu refers to.
+    fn variable(&mut self, part: &Part) -> Result<VariableRef<'strings>, String> {
+        let name = option(part)?;
+        Ok(self.target.find_variable(name).unwrap_or_else(|| {
+            self.target.add_variable(Variable {
+                name: name.to_owned(),
+                value: Constant::Number(0.0),
+            })
+        }))
+    }
+
+    /// Finds or creates the list that a dropdown menu refers to.
+    fn list(&mut self, part: &Part) -> Result<ListRef<'strings>, String> {
+        let name = option(part)?;
+        Ok(self.target.find_list(name).unwrap_or_else(|| {
+            self.target.add_list(List {
+                name: name.to_owned(),
+                items: Vec::new(),
+            })
+        }))
+    }
+}
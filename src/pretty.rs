@@ -0,0 +1,85 @@
+use std::io;
+
+/// Indents minified JSON as it is being written.
+pub struct PrettyWriter<W> {
+    inner: W,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// Whether the last byte opened an object or array, in which case the
+    /// newline is delayed so that empty ones stay on a single line.
+    opened: bool,
+}
+
+impl<W> PrettyWriter<W> {
+    pub const fn new(inner: W) -> Self {
+        Self {
+            inner,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            opened: false,
+        }
+    }
+
+    fn newline(&self, out: &mut Vec<u8>) {
+        out.push(b'\n');
+        out.resize(out.len() + self.depth * 2, b' ');
+    }
+}
+
+impl<W: io::Write> io::Write for PrettyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut out = Vec::with_capacity(buf.len() * 2);
+        for &byte in buf {
+            if self.in_string {
+                out.push(byte);
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            if std::mem::take(&mut self.opened) {
+                if matches!(byte, b'}' | b']') {
+                    out.push(byte);
+                    continue;
+                }
+                self.depth += 1;
+                self.newline(&mut out);
+            }
+
+            match byte {
+                b'{' | b'[' => {
+                    out.push(byte);
+                    self.opened = true;
+                }
+                b'}' | b']' => {
+                    self.depth -= 1;
+                    self.newline(&mut out);
+                    out.push(byte);
+                }
+                b',' => {
+                    out.push(byte);
+                    self.newline(&mut out);
+                }
+                b':' => out.extend_from_slice(b": "),
+                b'"' => {
+                    out.push(byte);
+                    self.in_string = true;
+                }
+                _ => out.push(byte),
+            }
+        }
+        self.inner.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
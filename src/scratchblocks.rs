@@ -0,0 +1,380 @@
+use crate::{
+    block::{Fields, Input, Opcode},
+    fold::Value,
+    BlockEntry, ListRef, ParameterKind, Project, RealTarget, VariableRef,
+};
+use std::{
+    error::Error,
+    fmt::{self, Write},
+};
+
+impl Project<'_> {
+    /// Renders the scripts of every target in [scratchblocks] syntax, the
+    /// format used on the Scratch forums and wiki, for reading the generated
+    /// code while debugging.
+    ///
+    /// Each target starts with a comment containing its name. Blocks that
+    /// have already been streamed and scripts of imported sprites are not
+    /// included.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a target uses a variable or
+    /// list of another sprite.
+    ///
+    /// [scratchblocks]: https://en.scratch-wiki.info/wiki/Block_Plugin/Syntax
+    pub fn to_scratchblocks(&self) -> Result<String, Box<dyn Error>> {
+        self.validate_owners()?;
+        let mut out = String::new();
+        for (i, target) in self.targets.iter().enumerate() {
+            if i != 0 {
+                out.push('\n');
+            }
+            target
+                .write_scratchblocks(&self.targets[0], &mut out)
+                .unwrap_or_else(|_| unreachable!());
+        }
+        Ok(out)
+    }
+}
+
+impl RealTarget<'_> {
+    fn write_scratchblocks(&self, stage: &Self, out: &mut String) -> fmt::Result {
+        writeln!(out, "// {}", self.name)?;
+        for comment in &self.comments {
+            for line in comment.text.lines() {
+                writeln!(out, "// {line}")?;
+            }
+        }
+        if self.block_id_offset != 0 {
+            writeln!(
+                out,
+                "// {} streamed blocks are not shown",
+                self.block_id_offset
+            )?;
+        }
+        if !self.raw_blocks.is_empty() {
+            writeln!(
+                out,
+                "// {} imported blocks are not shown",
+                self.raw_blocks.len()
+            )?;
+        }
+        let renderer = Renderer {
+            target: self,
+            stage,
+            blocks: self.blocks_with_data().collect(),
+        };
+        for entry in &renderer.blocks {
+            if entry.block.parent.is_none() {
+                writeln!(out)?;
+                renderer.stack(entry, 0, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Renderer<'target, 'strings> {
+    target: &'target RealTarget<'strings>,
+    stage: &'target RealTarget<'strings>,
+    /// Indexed by block ID, like the arena they come from.
+    blocks: Vec<BlockEntry<'target, 'strings>>,
+}
+
+impl<'target, 'strings> Renderer<'target, 'strings> {
+    fn stack<'this>(
+        &'this self,
+        mut entry: &'this BlockEntry<'target, 'strings>,
+        depth: usize,
+        out: &mut String,
+    ) -> fmt::Result {
+        loop {
+            self.block(entry, depth, out)?;
+            let Some(next) = entry.block.next else {
+                return Ok(());
+            };
+            entry = &self.blocks[next.0.to_u32() as usize];
+        }
+    }
+
+    fn block(&self, entry: &BlockEntry, depth: usize, out: &mut String) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        let header = match entry.block.opcode {
+            Opcode::control_forever => "forever".to_owned(),
+            Opcode::control_repeat => format!("repeat {}", self.input(entry, "TIMES")),
+            Opcode::control_repeat_until => {
+                format!("repeat until {}", self.input(entry, "CONDITION"))
+            }
+            // These two are not in the Scratch palette,
+            // so they need to be marked as control blocks.
+            Opcode::control_while => {
+                format!("while {} :: control", self.input(entry, "CONDITION"))
+            }
+            Opcode::control_for_each => format!(
+                "for each {} in {} :: control",
+                self.field(entry),
+                self.input(entry, "VALUE")
+            ),
+            Opcode::control_if | Opcode::control_if_else => {
+                format!("if {} then", self.input(entry, "CONDITION"))
+            }
+            _ => return writeln!(out, "{indent}{}", self.text(entry)),
+        };
+        writeln!(out, "{indent}{header}")?;
+        self.substack(entry, "SUBSTACK", depth + 1, out)?;
+        if matches!(entry.block.opcode, Opcode::control_if_else) {
+            writeln!(out, "{indent}else")?;
+            self.substack(entry, "SUBSTACK2", depth + 1, out)?;
+        }
+        writeln!(out, "{indent}end")
+    }
+
+    fn substack(
+        &self,
+        entry: &BlockEntry,
+        name: &str,
+        depth: usize,
+        out: &mut String,
+    ) -> fmt::Result {
+        match entry.input(name) {
+            Input::Substack(id) => self.stack(&self.blocks[id.0.to_u32() as usize], depth, out),
+            _ => Ok(()),
+        }
+    }
+
+    /// Renders a block that is not a C block, including reporters.
+    fn text(&self, entry: &BlockEntry) -> String {
+        let input = |name| self.input(entry, name);
+        let menu = |name| self.menu(entry, name);
+        match entry.block.opcode {
+            Opcode::argument_reporter_boolean => format!("<{}>", self.field(entry)),
+            Opcode::argument_reporter_string_number => format!("({})", self.field(entry)),
+            Opcode::control_create_clone_of => format!("create clone of {}", input("CLONE_OPTION")),
+            Opcode::control_create_clone_of_menu => "[myself v]".to_owned(),
+            Opcode::control_start_as_clone => "when I start as a clone".to_owned(),
+            Opcode::control_stop => format!("stop {}", self.field(entry)),
+            Opcode::control_wait => format!("wait {} seconds", input("DURATION")),
+            Opcode::control_forever
+            | Opcode::control_if
+            | Opcode::control_if_else
+            | Opcode::control_repeat
+            | Opcode::control_repeat_until
+            | Opcode::control_while
+            | Opcode::control_for_each => unreachable!(),
+            Opcode::event_broadcastandwait => {
+                format!("broadcast {} and wait", menu("BROADCAST_INPUT"))
+            }
+            Opcode::event_whenbroadcastreceived => format!("when I receive {}", self.field(entry)),
+            Opcode::event_whenflagclicked => "when flag clicked".to_owned(),
+            Opcode::event_whenkeypressed => format!("when {} key pressed", self.field(entry)),
+            Opcode::looks_gotofrontback => format!("go to {} layer", menu("FRONT_BACK")),
+            Opcode::looks_hide => "hide".to_owned(),
+            Opcode::looks_say => format!("say {}", input("MESSAGE")),
+            Opcode::looks_sayforsecs => {
+                format!("say {} for {} seconds", input("MESSAGE"), input("SECS"))
+            }
+            Opcode::looks_setsizeto => format!("set size to {} %", input("SIZE")),
+            Opcode::looks_show => "show".to_owned(),
+            Opcode::looks_switchcostumeto => format!("switch costume to {}", menu("COSTUME")),
+            Opcode::motion_changexby => format!("change x by {}", input("DX")),
+            Opcode::motion_changeyby => format!("change y by {}", input("DY")),
+            Opcode::motion_gotoxy => format!("go to x: {} y: {}", input("X"), input("Y")),
+            Opcode::motion_movesteps => format!("move {} steps", input("STEPS")),
+            Opcode::motion_setx => format!("set x to {}", input("X")),
+            Opcode::motion_sety => format!("set y to {}", input("Y")),
+            Opcode::motion_xposition => "(x position)".to_owned(),
+            Opcode::motion_yposition => "(y position)".to_owned(),
+            Opcode::pen_clear => "erase all".to_owned(),
+            Opcode::pen_penDown => "pen down".to_owned(),
+            Opcode::pen_penUp => "pen up".to_owned(),
+            Opcode::pen_setPenColorTo => format!("set pen color to {}", input("COLOR")),
+            Opcode::pen_setPenSizeTo => format!("set pen size to {}", input("SIZE")),
+            Opcode::pen_stamp => "stamp".to_owned(),
+            Opcode::procedures_call => self.custom_block(entry, false),
+            Opcode::procedures_definition => match entry.input("custom_block") {
+                Input::Prototype(prototype) => format!(
+                    "define {}",
+                    self.custom_block(&self.blocks[prototype.0.to_u32() as usize], true)
+                ),
+                _ => unreachable!(),
+            },
+            Opcode::procedures_prototype => self.custom_block(entry, true),
+            Opcode::sensing_answer => "(answer)".to_owned(),
+            Opcode::sensing_askandwait => format!("ask {} and wait", input("QUESTION")),
+            Opcode::sensing_keypressed => format!("<key {} pressed?>", menu("KEY_OPTION")),
+            Opcode::sensing_mousex => "(mouse x)".to_owned(),
+            Opcode::sensing_mousey => "(mouse y)".to_owned(),
+            Opcode::sensing_resettimer => "reset timer".to_owned(),
+            Opcode::sensing_timer => "(timer)".to_owned(),
+            _ => self.data(entry),
+        }
+    }
+
+    fn data(&self, entry: &BlockEntry) -> String {
+        let input = |name| self.input(entry, name);
+        match entry.block.opcode {
+            Opcode::data_addtolist => format!("add {} to {}", input("ITEM"), self.field(entry)),
+            Opcode::data_changevariableby => {
+                format!("change {} by {}", self.field(entry), input("VALUE"))
+            }
+            Opcode::data_deletealloflist => format!("delete all of {}", self.field(entry)),
+            Opcode::data_deleteoflist => {
+                format!("delete {} of {}", input("INDEX"), self.field(entry))
+            }
+            Opcode::data_hidelist => format!("hide list {}", self.field(entry)),
+            Opcode::data_hidevariable => format!("hide variable {}", self.field(entry)),
+            Opcode::data_insertatlist => format!(
+                "insert {} at {} of {}",
+                input("ITEM"),
+                input("INDEX"),
+                self.field(entry)
+            ),
+            Opcode::data_itemnumoflist => {
+                format!("(item # of {} in {})", input("ITEM"), self.field(entry))
+            }
+            Opcode::data_itemoflist => {
+                format!("(item {} of {})", input("INDEX"), self.field(entry))
+            }
+            Opcode::data_lengthoflist => format!("(length of {})", self.field(entry)),
+            Opcode::data_listcontents => match entry.fields {
+                Some(Fields::List(list)) => format!("({} :: list)", self.list_name(*list)),
+                _ => unreachable!(),
+            },
+            Opcode::data_listcontainsitem => {
+                format!("<{} contains {}?>", self.field(entry), input("ITEM"))
+            }
+            Opcode::data_replaceitemoflist => format!(
+                "replace item {} of {} with {}",
+                input("INDEX"),
+                self.field(entry),
+                input("ITEM")
+            ),
+            Opcode::data_setvariableto => {
+                format!("set {} to {}", self.field(entry), input("VALUE"))
+            }
+            Opcode::data_showlist => format!("show list {}", self.field(entry)),
+            Opcode::data_showvariable => format!("show variable {}", self.field(entry)),
+            _ => self.operator(entry),
+        }
+    }
+
+    fn operator(&self, entry: &BlockEntry) -> String {
+        let input = |name| self.input(entry, name);
+        match entry.block.opcode {
+            Opcode::operator_add => format!("({} + {})", input("NUM1"), input("NUM2")),
+            Opcode::operator_and => format!("<{} and {}>", input("OPERAND1"), input("OPERAND2")),
+            Opcode::operator_contains => {
+                format!("<{} contains {}?>", input("STRING1"), input("STRING2"))
+            }
+            Opcode::operator_divide => format!("({} / {})", input("NUM1"), input("NUM2")),
+            Opcode::operator_equals => format!("<{} = {}>", input("OPERAND1"), input("OPERAND2")),
+            Opcode::operator_gt => format!("<{} > {}>", input("OPERAND1"), input("OPERAND2")),
+            Opcode::operator_join => format!("(join {} {})", input("STRING1"), input("STRING2")),
+            Opcode::operator_length => format!("(length of {})", input("STRING")),
+            Opcode::operator_letter_of => {
+                format!("(letter {} of {})", input("LETTER"), input("STRING"))
+            }
+            Opcode::operator_lt => format!("<{} < {}>", input("OPERAND1"), input("OPERAND2")),
+            Opcode::operator_mathop => format!("({} of {})", self.field(entry), input("NUM")),
+            Opcode::operator_mod => format!("({} mod {})", input("NUM1"), input("NUM2")),
+            Opcode::operator_multiply => format!("({} * {})", input("NUM1"), input("NUM2")),
+            Opcode::operator_not => format!("<not {}>", input("OPERAND")),
+            Opcode::operator_or => format!("<{} or {}>", input("OPERAND1"), input("OPERAND2")),
+            Opcode::operator_random => {
+                format!("(pick random {} to {})", input("FROM"), input("TO"))
+            }
+            Opcode::operator_subtract => format!("({} - {})", input("NUM1"), input("NUM2")),
+            opcode => unreachable!("{opcode:?} is not an operator"),
+        }
+    }
+
+    /// Renders the label of a custom block with its parameters when it is
+    /// being defined or with its arguments when it is being used.
+    fn custom_block(&self, entry: &BlockEntry, is_prototype: bool) -> String {
+        let block = entry.mutation.unwrap_or_else(|| unreachable!()).0;
+        let block = &self.target.custom_blocks[block.0];
+        let mut label = escape(&block.name);
+        for (parameter, (_, input)) in self.target.parameters[block.parameters]
+            .iter()
+            .zip(entry.inputs)
+        {
+            let part = if is_prototype {
+                let name = escape(&parameter.name);
+                match parameter.kind {
+                    ParameterKind::StringOrNumber => format!("({name})"),
+                    ParameterKind::Boolean => format!("<{name}>"),
+                }
+            } else {
+                self.operand(input)
+            };
+            label.push(' ');
+            label.push_str(&part);
+        }
+        label
+    }
+
+    fn input(&self, entry: &BlockEntry, name: &str) -> String {
+        self.operand(entry.input(name))
+    }
+
+    /// Renders an input that is a dropdown menu in Scratch, such as the key
+    /// of a `key pressed?` block, as a menu when it is a constant.
+    fn menu(&self, entry: &BlockEntry, name: &str) -> String {
+        match entry.input(name) {
+            Input::String(s) => format!("[{} v]", escape(s)),
+            input => self.operand(input),
+        }
+    }
+
+    fn operand(&self, input: &Input) -> String {
+        match *input {
+            Input::Substack(id) | Input::Prototype(id) => {
+                self.text(&self.blocks[id.0.to_u32() as usize])
+            }
+            // Only boolean inputs are left empty.
+            Input::EmptySubstack => "<>".to_owned(),
+            Input::Number(n) => format!("({})", Value::Number(n)),
+            Input::String(ref s) => format!("[{}]", escape(s)),
+            Input::Boolean(_) | Input::Reporter(..) => unreachable!(),
+            Input::Variable(variable) => format!("({})", self.variable_name(variable)),
+            Input::List(list) => format!("({} :: list)", self.list_name(list)),
+        }
+    }
+
+    fn variable_name(&self, variable: VariableRef) -> String {
+        let owner = self.target.owner(self.stage, variable.target);
+        escape(&owner.variables[variable.id].name)
+    }
+
+    fn list_name(&self, list: ListRef) -> String {
+        let owner = self.target.owner(self.stage, list.target);
+        escape(&owner.lists[list.id].name)
+    }
+
+    fn field(&self, entry: &BlockEntry) -> String {
+        match entry.fields.unwrap_or_else(|| unreachable!()) {
+            Fields::Variable(variable) => format!("[{} v]", self.variable_name(*variable)),
+            Fields::List(list) => format!("[{} v]", self.list_name(*list)),
+            Fields::Value(parameter) => escape(&self.target.parameters[*parameter].name),
+            Fields::Operator(operator) => format!("[{operator} v]"),
+            Fields::KeyOption(key) => format!("[{} v]", escape(key)),
+            Fields::BroadcastOption(message) => format!("[{} v]", escape(message)),
+            Fields::StopAll => "[all v]".to_owned(),
+            Fields::StopThisScript => "[this script v]".to_owned(),
+            Fields::CloneSelf => "[myself v]".to_owned(),
+        }
+    }
+}
+
+/// Escapes the characters that would otherwise start or end an input.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '[' | ']' | '(' | ')' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,53 @@
+use std::{borrow::Cow, io};
+
+/// Sounds can currently only be imported, not created.
+pub struct Sound<'strings> {
+    name: Cow<'strings, str>,
+    data_format: Box<str>,
+    digest: md5::Digest,
+    content: Vec<u8>,
+    rate: u32,
+    sample_count: u64,
+}
+
+impl<'strings> Sound<'strings> {
+    pub(crate) fn new(
+        name: Cow<'strings, str>,
+        data_format: Box<str>,
+        content: Vec<u8>,
+        rate: u32,
+        sample_count: u64,
+    ) -> Self {
+        let digest = md5::compute(&content);
+        Self {
+            name,
+            data_format,
+            digest,
+            content,
+            rate,
+            sample_count,
+        }
+    }
+
+    pub(crate) fn serialize(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+        write!(
+            writer,
+            r#"{{"name":{:?},"dataFormat":{:?},"assetId":"{:x}","md5ext":"{:x}.{}","rate":{},"sampleCount":{}}}"#,
+            self.name,
+            self.data_format,
+            self.digest,
+            self.digest,
+            self.data_format.escape_debug(),
+            self.rate,
+            self.sample_count,
+        )
+    }
+
+    pub(crate) fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub(crate) fn file_name(&self) -> String {
+        format!("{:x}.{}", self.digest, self.data_format)
+    }
+}
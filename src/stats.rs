@@ -0,0 +1,77 @@
+use crate::{Project, RealTarget};
+use std::{collections::BTreeMap, error::Error, io};
+
+/// Statistics about a [`Project`], as returned by [`Project::stats`].
+pub struct Stats<'project> {
+    /// The size of the `project.json` file in bytes
+    /// when written with the default [`FinishOptions`].
+    ///
+    /// [`FinishOptions`]: crate::FinishOptions
+    pub project_json_size: u64,
+    pub targets: Vec<TargetStats<'project>>,
+}
+
+pub struct TargetStats<'project> {
+    pub name: &'project str,
+    /// The number of blocks with each opcode.
+    pub blocks: BTreeMap<String, usize>,
+    pub variables: usize,
+    pub lists: usize,
+    /// The total size of all costumes and sounds in bytes.
+    pub asset_bytes: u64,
+}
+
+impl Project<'_> {
+    /// Computes statistics about the [`Project`] without writing it anywhere.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a target uses a variable or
+    /// list of another sprite.
+    pub fn stats(&self) -> Result<Stats<'_>, Box<dyn Error>> {
+        Ok(Stats {
+            project_json_size: self.project_json_size()?,
+            targets: self.targets.iter().map(RealTarget::stats).collect(),
+        })
+    }
+
+    fn project_json_size(&self) -> io::Result<u64> {
+        let mut counter = ByteCounter(0);
+        self.serialize(&mut counter)?;
+        Ok(counter.0)
+    }
+}
+
+impl RealTarget<'_> {
+    fn stats(&self) -> TargetStats<'_> {
+        let mut blocks = self.streamed_opcodes.clone();
+        for block in self.blocks.values() {
+            *blocks.entry(format!("{:?}", block.opcode)).or_default() += 1;
+        }
+        for block in &self.raw_blocks {
+            if let Some(opcode) = &block.opcode {
+                *blocks.entry(opcode.clone()).or_default() += 1;
+            }
+        }
+        TargetStats {
+            name: &self.name,
+            blocks,
+            variables: self.variables.len(),
+            lists: self.lists.len(),
+            asset_bytes: self.assets().map(|asset| asset.content.len() as u64).sum(),
+        }
+    }
+}
+
+struct ByteCounter(u64);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
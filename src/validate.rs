@@ -0,0 +1,331 @@
+use crate::{
+    block::{self, Fields, Input, InputName, Opcode},
+    monitor::Monitor,
+    BlockEntry, Project, RealTarget,
+};
+use std::error::Error;
+
+impl Project<'_> {
+    /// Checks that the blocks of every target are consistent with each other.
+    /// This includes:
+    /// - every block having as many inputs and fields as its opcode requires,
+    /// - inputs having the names and fields having the kind that their block
+    ///   requires,
+    /// - IDs of parents, next blocks and inputs referring to existing blocks,
+    /// - parents referring back to their children,
+    /// - no block being its own ancestor,
+    /// - variables and lists of blocks and monitors belonging to the target
+    ///   itself or the stage.
+    ///
+    /// Blocks that have already been streamed are not checked.
+    /// [`Project::finish`] calls this in debug builds, and checks the
+    /// variables and lists in every build.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error describing the first problem found.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        for target in &self.targets {
+            target
+                .validate()
+                .map_err(|err| format!("invalid target {:?}: {err}", target.name))?;
+        }
+        Ok(())
+    }
+
+    /// Checks the part of [`Project::validate`] that serializing relies on,
+    /// which is that variables and lists belong to the target that uses them
+    /// or the stage.
+    pub(crate) fn validate_owners(&self) -> Result<(), String> {
+        for target in &self.targets {
+            target
+                .validate_owners()
+                .map_err(|err| format!("invalid target {:?}: {err}", target.name))?;
+        }
+        Ok(())
+    }
+}
+
+impl RealTarget<'_> {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        self.validate_counts()?;
+        self.validate_owners()?;
+
+        let block_count = self.blocks.len();
+        let check_id = |id: block::Id, what: &str, owner: tec::Id<block::Block>| {
+            if index(id) < block_count {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{what} of block b{} refers to b{}, which doesn't exist",
+                    owner.to_u32(),
+                    id.0.to_u32()
+                ))
+            }
+        };
+
+        // Only complete blocks are known to be safe to look at once the
+        // counts have been checked.
+        let entries = self.blocks_with_data().collect::<Vec<_>>();
+        for entry in &entries {
+            if let Some(parent) = entry.block.parent {
+                check_id(parent, "parent", entry.id)?;
+            }
+            self.validate_shape(entry)?;
+            if let Some(next) = entry.block.next {
+                check_id(next, "next block", entry.id)?;
+                let child = &self.blocks[next.0];
+                if child
+                    .parent
+                    .is_none_or(|it| it.0.to_u32() != entry.id.to_u32())
+                {
+                    return Err(format!(
+                        "block b{} is followed by b{}, whose parent is not b{}",
+                        entry.id.to_u32(),
+                        next.0.to_u32(),
+                        entry.id.to_u32()
+                    ));
+                }
+            }
+            for (name, input) in entry.inputs {
+                let (Input::Substack(child) | Input::Prototype(child)) = *input else {
+                    continue;
+                };
+                check_id(child, "an input", entry.id)?;
+                if self.blocks[child.0]
+                    .parent
+                    .is_none_or(|it| it.0.to_u32() != entry.id.to_u32())
+                {
+                    return Err(format!(
+                        "input {name} of block b{} is b{}, whose parent is not b{}",
+                        entry.id.to_u32(),
+                        child.0.to_u32(),
+                        entry.id.to_u32()
+                    ));
+                }
+            }
+        }
+
+        for entry in &entries {
+            let Some(parent) = entry.block.parent else {
+                continue;
+            };
+            let parent_entry = &entries[index(parent)];
+            let is_child = parent_entry
+                .block
+                .next
+                .is_some_and(|it| it.0.to_u32() == entry.id.to_u32())
+                || parent_entry.inputs.iter().any(|(_, input)| {
+                    matches!(
+                        *input,
+                        Input::Substack(it) | Input::Prototype(it)
+                            if it.0.to_u32() == entry.id.to_u32()
+                    )
+                });
+            if !is_child {
+                return Err(format!(
+                    "block b{} has b{} as its parent, which doesn't refer to it",
+                    entry.id.to_u32(),
+                    parent.0.to_u32()
+                ));
+            }
+
+            // A chain of parents longer than the number of blocks must
+            // contain a cycle.
+            let mut ancestor = parent;
+            for _ in 0..block_count {
+                match self.blocks[ancestor.0].parent {
+                    Some(it) => ancestor = it,
+                    None => break,
+                }
+            }
+            if self.blocks[ancestor.0].parent.is_some() {
+                return Err(format!(
+                    "block b{} is part of a cycle of parents",
+                    entry.id.to_u32()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the inputs of a block have the names that its opcode
+    /// requires and that its fields are of the kind that it needs. Both are
+    /// only matched to blocks by their order, so a mismatch means that they
+    /// belong to another block.
+    fn validate_shape(&self, entry: &BlockEntry) -> Result<(), String> {
+        let names = entry.inputs.iter().map(|(name, _)| *name);
+        let mismatch = if let Some(expected) = entry.block.opcode.input_names() {
+            names.zip(expected).position(
+                |(name, expected)| !matches!(name, InputName::Static(it) if it == *expected),
+            )
+        } else {
+            let mutation = entry.mutation.unwrap_or_else(|| unreachable!());
+            let parameters = self.custom_blocks[mutation.0 .0].parameters;
+            names.zip(parameters).position(|(name, parameter)| {
+                !matches!(name, InputName::Parameter(it) if it.to_u32() == parameter.to_u32())
+            })
+        };
+        if let Some(i) = mismatch {
+            return Err(format!(
+                "input {i} of block b{} ({:?}) is named {}, which it doesn't have there",
+                entry.id.to_u32(),
+                entry.block.opcode,
+                entry.inputs[i].0
+            ));
+        }
+        if entry
+            .fields
+            .is_some_and(|fields| !fields_fit(fields, entry.block.opcode))
+        {
+            return Err(format!(
+                "block b{} ({:?}) has the fields of another kind of block",
+                entry.id.to_u32(),
+                entry.block.opcode
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the blocks and monitors of the target only use variables
+    /// and lists of the target itself or the stage, which are the only ones
+    /// that Scratch can find.
+    pub(crate) fn validate_owners(&self) -> Result<(), String> {
+        for entry in self.blocks_with_data() {
+            self.validate_block_owners(&entry)?;
+        }
+        for monitor in &self.monitors {
+            let owner = match *monitor {
+                Monitor::Variable(variable, _) => variable.target,
+                Monitor::List(list, _) => list.target,
+            };
+            if owner != self.index && owner != 0 {
+                return Err("a monitor shows a variable or list of another sprite".to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_block_owners(&self, entry: &BlockEntry) -> Result<(), String> {
+        let owners = entry
+            .inputs
+            .iter()
+            .filter_map(|(_, input)| match *input {
+                Input::Variable(variable) => Some(variable.target),
+                Input::List(list) => Some(list.target),
+                _ => None,
+            })
+            .chain(match entry.fields {
+                Some(&Fields::Variable(variable)) => Some(variable.target),
+                Some(&Fields::List(list)) => Some(list.target),
+                _ => None,
+            });
+        for owner in owners {
+            if owner != self.index && owner != 0 {
+                return Err(format!(
+                    "block b{} uses a variable or list of another sprite",
+                    entry.id.to_u32()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the inputs, fields and mutations, which are stored
+    /// separately from the blocks, add up to what the opcodes require.
+    fn validate_counts(&self) -> Result<(), String> {
+        let mut inputs = 0;
+        let mut fields = 0;
+        let mut mutations = self.mutations.values();
+        for (id, block) in &self.blocks {
+            let input_count = if let Some(count) = block.opcode.input_count() {
+                count
+            } else {
+                let mutation = mutations.next().ok_or_else(|| {
+                    format!(
+                        "block b{} ({:?}) has no mutation",
+                        id.to_u32(),
+                        block.opcode
+                    )
+                })?;
+                self.custom_blocks[mutation.0 .0].parameters.len() as usize
+            };
+            let remaining = self.inputs.len() - inputs;
+            if input_count > remaining {
+                return Err(format!(
+                    "block b{} ({:?}) needs {input_count} inputs but only {remaining} are left",
+                    id.to_u32(),
+                    block.opcode,
+                ));
+            }
+            inputs += input_count;
+            fields += usize::from(block.opcode.has_fields());
+            if fields > self.fields.len() {
+                return Err(format!(
+                    "block b{} ({:?}) needs fields but none are left",
+                    id.to_u32(),
+                    block.opcode
+                ));
+            }
+        }
+        if inputs != self.inputs.len() {
+            return Err(format!(
+                "the blocks need {inputs} inputs but there are {}",
+                self.inputs.len()
+            ));
+        }
+        if fields != self.fields.len() {
+            return Err(format!(
+                "the blocks need {fields} fields but there are {}",
+                self.fields.len()
+            ));
+        }
+        if mutations.next().is_some() {
+            return Err("there are more mutations than custom block blocks".to_owned());
+        }
+        Ok(())
+    }
+}
+
+/// Whether a block with the given opcode can have the fields.
+const fn fields_fit(fields: &Fields, opcode: Opcode) -> bool {
+    match fields {
+        Fields::Variable(_) => matches!(
+            opcode,
+            Opcode::control_for_each
+                | Opcode::data_changevariableby
+                | Opcode::data_hidevariable
+                | Opcode::data_setvariableto
+                | Opcode::data_showvariable
+        ),
+        Fields::List(_) => matches!(
+            opcode,
+            Opcode::data_addtolist
+                | Opcode::data_deletealloflist
+                | Opcode::data_deleteoflist
+                | Opcode::data_hidelist
+                | Opcode::data_insertatlist
+                | Opcode::data_itemnumoflist
+                | Opcode::data_itemoflist
+                | Opcode::data_lengthoflist
+                | Opcode::data_listcontents
+                | Opcode::data_listcontainsitem
+                | Opcode::data_replaceitemoflist
+                | Opcode::data_showlist
+        ),
+        Fields::Value(_) => matches!(
+            opcode,
+            Opcode::argument_reporter_boolean | Opcode::argument_reporter_string_number
+        ),
+        Fields::Operator(_) => matches!(opcode, Opcode::operator_mathop),
+        Fields::KeyOption(_) => matches!(opcode, Opcode::event_whenkeypressed),
+        Fields::BroadcastOption(_) => matches!(opcode, Opcode::event_whenbroadcastreceived),
+        Fields::StopAll | Fields::StopThisScript => matches!(opcode, Opcode::control_stop),
+        Fields::CloneSelf => matches!(opcode, Opcode::control_create_clone_of_menu),
+    }
+}
+
+const fn index(id: block::Id) -> usize {
+    id.0.to_u32() as usize
+}
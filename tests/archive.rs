@@ -0,0 +1,90 @@
+//! Checks that building the same project twice produces byte-identical
+//! `.sb3` files.
+
+use sb3_builder::{block, BlockRef, Constant, Costume, InsertionPoint, List, Project, Variable};
+use std::{env, fs, io, path::PathBuf};
+
+fn costume(name: &'static str, svg: &str) -> Costume<'static> {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("archive-{name}.svg"));
+    fs::write(&path, svg).unwrap_or_else(|err| panic!("failed to write costume: {err}"));
+    Costume::from_file(name, &path).unwrap_or_else(|err| panic!("failed to read costume: {err}"))
+}
+
+fn reproducible_project() -> Project<'static> {
+    let mut project = Project::default();
+    project.stage().add_costume(costume(
+        "backdrop1",
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="480" height="360"/>"#,
+    ));
+    for name in ["Sprite1", "Sprite2"] {
+        let mut sprite = project.add_sprite(name);
+        // Added in a different order than their file names sort in.
+        sprite.add_costume(costume(
+            "costume2",
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="4" height="4"/>"#,
+        ));
+        sprite.add_costume(costume(
+            "costume1",
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="2" height="2"/>"#,
+        ));
+        let counter = sprite.add_variable(Variable {
+            name: "counter".to_owned(),
+            value: Constant::Number(0.0),
+        });
+        let log = sprite.add_list(List {
+            name: "log".to_owned(),
+            items: vec![Constant::String("started".into())],
+        });
+        sprite.start_script(block::when_flag_clicked());
+        let _: InsertionPoint = sprite.repeat(10.0.into());
+        let _: BlockRef = sprite.put(block::change_variable(counter, 1.0.into()));
+        let _: BlockRef = sprite.put(block::append(log, counter.into()));
+    }
+    project
+}
+
+fn build() -> Vec<u8> {
+    let mut archive = io::Cursor::new(Vec::new());
+    reproducible_project()
+        .finish(&mut archive)
+        .unwrap_or_else(|err| panic!("failed to finish: {err}"));
+    archive.into_inner()
+}
+
+#[test]
+fn archives_have_fixed_timestamps_and_sorted_assets() {
+    let archive = build();
+    let archive = rawzip::ZipArchive::from_slice(&archive)
+        .unwrap_or_else(|err| panic!("invalid archive: {err}"));
+    let mut names = Vec::new();
+    for entry in archive.entries() {
+        let entry = entry.unwrap_or_else(|err| panic!("invalid entry: {err}"));
+        let name = String::from_utf8_lossy(entry.file_path().as_ref()).into_owned();
+        let time = entry.last_modified();
+        assert_eq!(
+            (
+                time.year(),
+                time.month(),
+                time.day(),
+                time.hour(),
+                time.minute(),
+                time.second()
+            ),
+            (1980, 1, 1, 0, 0, 0),
+            "{name} has the modification time {time}"
+        );
+        names.push(name);
+    }
+
+    assert_eq!(names.pop().as_deref(), Some("project.json"));
+    let mut sorted = names.clone();
+    sorted.sort_unstable();
+    sorted.dedup();
+    assert_eq!(names, sorted);
+    assert_eq!(names.len(), 3);
+}
+
+#[test]
+fn builds_are_byte_identical() {
+    assert!(build() == build());
+}
@@ -0,0 +1,886 @@
+//! Builds projects that use every block constructor and [`Target`] method,
+//! checks their `project.json` with the `jsonschema` crate against a
+//! transcription of the sb3 schema of scratch-parser and compares it with the
+//! files in `tests/golden`.
+//!
+//! Run with `UPDATE_GOLDEN=1` to rewrite the golden files after an
+//! intentional change to the output.
+
+use sb3_builder::{
+    block::{self, DeleteIndex, ListIndex},
+    BlockRef, CloudVariableError, Constant, Costume, FinishOptions, InsertionPoint, List,
+    ListMonitorOptions, MonitorMode, MonitorOptions, Parameter, ParameterKind, Project, Target,
+    Variable,
+};
+use std::{collections::HashSet, env, fs, io, path::PathBuf, sync::LazyLock};
+
+const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="2" height="2"/>"#;
+
+fn costume(name: &'static str) -> Costume<'static> {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden-costume.svg");
+    fs::write(&path, SVG).unwrap_or_else(|err| panic!("failed to write costume: {err}"));
+    Costume::from_file(name, &path).unwrap_or_else(|err| panic!("failed to read costume: {err}"))
+}
+
+/// Every target needs a costume to be valid.
+fn new_project() -> Project<'static> {
+    let mut project = Project::default();
+    project.stage().add_costume(costume("backdrop1"));
+    project
+}
+
+fn add_sprite<'project>(
+    project: &'project mut Project<'static>,
+    name: &'static str,
+) -> Target<'static, 'project> {
+    let mut sprite = project.add_sprite(name);
+    sprite.add_costume(costume("costume1"));
+    sprite
+}
+
+fn put_all(
+    target: &mut Target<'static, '_>,
+    blocks: impl IntoIterator<Item = block::Stacking<'static>>,
+) {
+    for block in blocks {
+        let _: BlockRef = target.put(block);
+    }
+}
+
+const fn go_to(target: &mut Target<'static, '_>, point: InsertionPoint<'static>) {
+    let _: InsertionPoint = target.insert_at(point);
+}
+
+static SB3_SCHEMA: LazyLock<jsonschema::Validator> = LazyLock::new(|| {
+    let schema = serde_json::from_str(include_str!("schema/sb3_schema.json"))
+        .unwrap_or_else(|err| panic!("invalid schema: {err}"));
+    jsonschema::draft4::new(&schema).unwrap_or_else(|err| panic!("invalid schema: {err}"))
+});
+
+fn check(name: &str, project: Project<'static>) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("golden-{name}"));
+    let options = FinishOptions {
+        pretty: true,
+        ..FinishOptions::default()
+    };
+    project
+        .finish_to_dir(&dir, &options)
+        .unwrap_or_else(|err| panic!("failed to finish {name}: {err}"));
+    let json = fs::read_to_string(dir.join("project.json"))
+        .unwrap_or_else(|err| panic!("failed to read project.json: {err}"));
+
+    let value = serde_json::from_str(&json)
+        .unwrap_or_else(|err| panic!("{name} is not valid JSON: {err}\n{json}"));
+    if let Err(err) = SB3_SCHEMA.validate(&value) {
+        panic!(
+            "{name} doesn't match the sb3 schema at {}: {err}",
+            err.instance_path()
+        );
+    }
+    check_ids(name, &value);
+
+    let golden = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.json"));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(golden.parent().unwrap_or_else(|| unreachable!()))
+            .unwrap_or_else(|err| panic!("failed to create golden directory: {err}"));
+        fs::write(&golden, &json).unwrap_or_else(|err| panic!("failed to write golden: {err}"));
+        return;
+    }
+    let expected = fs::read_to_string(&golden).unwrap_or_else(|err| {
+        panic!(
+            "failed to read {}: {err}; run with UPDATE_GOLDEN=1",
+            golden.display()
+        )
+    });
+    assert!(
+        json == expected,
+        "{name} differs from {}; run with UPDATE_GOLDEN=1 to update it\n{json}",
+        golden.display()
+    );
+}
+
+/// Checks that variables and lists have IDs that are unique in the project
+/// and that every monitor has the ID of one, which Scratch relies on to find
+/// the variable of a monitor.
+fn check_ids(name: &str, project: &serde_json::Value) {
+    let mut ids = HashSet::new();
+    for target in project["targets"].as_array().into_iter().flatten() {
+        for key in ["variables", "lists"] {
+            for id in target[key]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(id, _)| id)
+            {
+                assert!(
+                    ids.insert(id.as_str()),
+                    "{name} has two variables with ID {id}"
+                );
+            }
+        }
+    }
+    for monitor in project["monitors"].as_array().into_iter().flatten() {
+        let id = monitor["id"].as_str().unwrap_or_default();
+        assert!(
+            ids.contains(id),
+            "{name} has a monitor without a variable: {id}"
+        );
+    }
+}
+
+#[test]
+fn stacking_blocks() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    let variable = sprite.add_variable(Variable {
+        name: "counter".to_owned(),
+        value: Constant::Number(0.0),
+    });
+    let list = sprite.add_list(List {
+        name: "items".to_owned(),
+        items: vec![
+            Constant::String("a".into()),
+            Constant::Number(1.5),
+            Constant::Number(f64::NAN),
+        ],
+    });
+
+    sprite.start_script(block::when_flag_clicked());
+    put_all(
+        &mut sprite,
+        [
+            block::append(list, "x".into()),
+            block::ask("What?".into()),
+            block::broadcast_and_wait("message1".into()),
+            block::change_variable(variable, 1.0.into()),
+            block::change_x(10.0.into()),
+            block::change_y((-10.0).into()),
+            block::delete_all_of_list(list),
+            block::delete_of_list(list, DeleteIndex::At(1.0.into())),
+            block::erase_all(),
+            block::go_to_back_layer(),
+            block::go_to_front_layer(),
+            block::go_to_xy(0.0.into(), 0.0.into()),
+            block::hide(),
+            block::insert_at_list(list, "y".into(), ListIndex::Last),
+            block::move_steps(5.0.into()),
+            block::pen_down(),
+            block::pen_up(),
+            block::replace(list, ListIndex::Random, "z".into()),
+            block::reset_timer(),
+            block::say("Hello!".into()),
+            block::say_for_seconds("Hmm...".into(), 2.0.into()),
+            block::set_costume("costume1".into()),
+            block::set_pen_color("#ff0000".into()),
+            block::set_pen_size(3.0.into()),
+            block::set_size(100.0.into()),
+            block::set_variable(variable, "text".into()),
+            block::set_x(1.0.into()),
+            block::set_y(2.0.into()),
+            block::show(),
+            block::stamp(),
+            block::wait(0.5.into()),
+            block::stop_this_script(),
+        ],
+    );
+
+    sprite.start_script(block::when_key_pressed("space"));
+    put_all(&mut sprite, [block::stop_all()]);
+    sprite.start_script(block::when_cloned());
+    put_all(&mut sprite, [block::say("clone".into())]);
+    sprite.start_script(block::when_received("message1"));
+    put_all(&mut sprite, [block::say("received".into())]);
+
+    check("stacking_blocks", project);
+}
+
+#[test]
+fn reporters() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    let variable = sprite.add_variable(Variable {
+        name: "v".to_owned(),
+        value: Constant::String("value".into()),
+    });
+    let list = sprite.add_list(List {
+        name: "l".to_owned(),
+        items: Vec::new(),
+    });
+
+    sprite.start_script(block::when_flag_clicked());
+    let say = |sprite: &mut Target<'static, '_>, operand| {
+        put_all(sprite, [block::say(operand)]);
+    };
+
+    let x = sprite.x_position();
+    let y = sprite.y_position();
+    let sum = sprite.add(x, y);
+    let timer = sprite.timer();
+    let difference = sprite.sub(sum, timer);
+    let answer = sprite.answer();
+    let product = sprite.mul(difference, answer);
+    let quotient = sprite.div(product, variable.into());
+    let remainder = sprite.modulo(quotient, 7.0.into());
+    say(&mut sprite, remainder);
+
+    let mouse_x = sprite.mouse_x();
+    let mouse_y = sprite.mouse_y();
+    let lt = sprite.lt(mouse_x, mouse_y);
+    let key = sprite.key_is_pressed("space".into());
+    let and = sprite.and(lt, key);
+    let eq = sprite.eq(variable.into(), "value".into());
+    let gt = sprite.gt(variable.into(), 1.0.into());
+    let or = sprite.or(eq, gt);
+    let not = sprite.not(or);
+    let both = sprite.and(and, not);
+    say(&mut sprite, both);
+
+    let item = sprite.item_of_list(list, ListIndex::At(1.0.into()));
+    let item_num = sprite.item_num_of_list(list, "a".into());
+    let length = sprite.length(item);
+    let list_length = sprite.length_of_list(list);
+    let letter = sprite.letter_of(1.0.into(), "abc".into());
+    let joined = sprite.join(letter, length);
+    let joined = sprite.join(joined, item_num);
+    let joined = sprite.join(joined, list_length);
+    let contents = sprite.list_contents(list);
+    let joined = sprite.join(joined, contents);
+    say(&mut sprite, joined);
+
+    let contains = sprite.contains("apple".into(), "a".into());
+    let list_contains = sprite.list_contains_item(list, "a".into());
+    let both = sprite.and(contains, list_contains);
+    say(&mut sprite, both);
+
+    let random = sprite.random(1.0.into(), 10.0.into());
+    let sqrt = sprite.mathop("sqrt", random);
+    say(&mut sprite, sqrt);
+    say(&mut sprite, list.into());
+    say(&mut sprite, f64::INFINITY.into());
+    say(&mut sprite, String::from("owned \"quoted\" string").into());
+
+    check("reporters", project);
+}
+
+#[test]
+fn control_flow() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    let i = sprite.add_variable(Variable {
+        name: "i".to_owned(),
+        value: Constant::Number(0.0),
+    });
+
+    sprite.start_script(block::when_flag_clicked());
+    let after_repeat = sprite.repeat(10.0.into());
+    put_all(&mut sprite, [block::move_steps(1.0.into())]);
+    go_to(&mut sprite, after_repeat);
+
+    let after_for = sprite.for_(i, 5.0.into());
+    put_all(&mut sprite, [block::say(i.into())]);
+    go_to(&mut sprite, after_for);
+
+    let condition = sprite.gt(i.into(), 3.0.into());
+    let after_if = sprite.if_(condition);
+    put_all(&mut sprite, [block::hide()]);
+    go_to(&mut sprite, after_if);
+
+    let condition = sprite.lt(i.into(), 3.0.into());
+    let [after_if_else, else_] = sprite.if_else(condition);
+    put_all(&mut sprite, [block::show()]);
+    go_to(&mut sprite, else_);
+    put_all(&mut sprite, [block::hide()]);
+    go_to(&mut sprite, after_if_else);
+
+    let condition = sprite.lt(i.into(), 10.0.into());
+    let after_while = sprite.while_(condition);
+    put_all(&mut sprite, [block::change_variable(i, 1.0.into())]);
+    go_to(&mut sprite, after_while);
+
+    let condition = sprite.eq(i.into(), 0.0.into());
+    let after_until = sprite.repeat_until(condition);
+    put_all(&mut sprite, [block::change_variable(i, (-1.0).into())]);
+    go_to(&mut sprite, after_until);
+
+    sprite.clone_self();
+    sprite.forever();
+    put_all(&mut sprite, [block::wait(1.0.into())]);
+
+    check("control_flow", project);
+}
+
+#[test]
+fn custom_blocks() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    let parameters = [
+        Parameter {
+            name: "text".to_owned(),
+            kind: ParameterKind::StringOrNumber,
+        },
+        Parameter {
+            name: "loud".to_owned(),
+            kind: ParameterKind::Boolean,
+        },
+    ];
+    let (speak, body) = sprite.add_custom_block("speak".to_owned(), parameters.into_iter());
+    let (empty, _) = sprite.add_custom_block("nothing".to_owned(), std::iter::empty());
+
+    let script = sprite.insert_at(body);
+    let text = sprite.custom_block_parameter(speak, 0);
+    let loud = sprite.custom_block_parameter(speak, 1);
+    let after_if = sprite.if_(loud);
+    let shout = sprite.join(text, "!".into());
+    put_all(&mut sprite, [block::say(shout)]);
+    go_to(&mut sprite, after_if);
+    go_to(&mut sprite, script);
+
+    sprite.start_script(block::when_flag_clicked());
+    let loud = sprite.eq(1.0.into(), 1.0.into());
+    sprite.use_custom_block(speak, vec!["hi".into(), loud]);
+    sprite.use_custom_block(empty, Vec::new());
+
+    check("custom_blocks", project);
+}
+
+#[test]
+fn targets() {
+    let mut project = new_project();
+    let mut stage = project.stage();
+    let global = stage.add_variable(Variable {
+        name: "score".to_owned(),
+        value: Constant::Number(0.0),
+    });
+    let _: sb3_builder::ListRef = stage.add_list(List {
+        name: "high scores".to_owned(),
+        items: vec![Constant::Number(f64::NEG_INFINITY)],
+    });
+    let visits = stage
+        .add_cloud_variable(Variable {
+            name: "visits".to_owned(),
+            value: Constant::Number(0.0),
+        })
+        .unwrap_or_else(|err| panic!("failed to add cloud variable: {err}"));
+    stage.show_variable_monitor(
+        global,
+        MonitorOptions {
+            mode: MonitorMode::Large,
+            x: 5.0,
+            y: 5.0,
+            ..MonitorOptions::default()
+        },
+    );
+    stage.add_comment("A comment on the stage.".to_owned());
+    stage.start_script(block::when_received("start"));
+    put_all(
+        &mut stage,
+        [
+            block::set_variable(global, 0.0.into()),
+            block::change_variable(visits, 1.0.into()),
+        ],
+    );
+
+    let mut sprite = add_sprite(&mut project, "Player");
+    sprite.add_costume(costume("costume2"));
+    let _: sb3_builder::VariableRef = sprite.add_variable(Variable {
+        name: "lives".to_owned(),
+        value: Constant::Number(3.0),
+    });
+    let lives = sprite
+        .find_variable("lives")
+        .unwrap_or_else(|| unreachable!());
+    // Sprites can use the variables of the stage, including cloud variables.
+    let score = sprite
+        .find_variable("score")
+        .unwrap_or_else(|| unreachable!());
+    let _: sb3_builder::ListRef = sprite.add_list(List {
+        name: "inventory".to_owned(),
+        items: Vec::new(),
+    });
+    let inventory = sprite
+        .find_list("inventory")
+        .unwrap_or_else(|| unreachable!());
+    sprite.show_variable_monitor(
+        lives,
+        MonitorOptions {
+            mode: MonitorMode::Slider,
+            y: 40.0,
+            max: 3.0,
+            visible: false,
+            ..MonitorOptions::default()
+        },
+    );
+    sprite.show_list_monitor(
+        inventory,
+        ListMonitorOptions {
+            x: 360.0,
+            width: 120.0,
+            height: 200.0,
+            ..ListMonitorOptions::default()
+        },
+    );
+    sprite.start_script(block::when_flag_clicked());
+    put_all(
+        &mut sprite,
+        [
+            block::set_variable(lives, 3.0.into()),
+            block::show_variable(lives),
+            block::delete_all_of_list(inventory),
+            block::hide_list(inventory),
+            block::broadcast_and_wait("start".into()),
+            block::change_variable(score, 1.0.into()),
+            block::set_variable(visits, score.into()),
+        ],
+    );
+
+    let _: Target = add_sprite(&mut project, "Empty");
+
+    check("targets", project);
+}
+
+#[test]
+fn cloud_variables_follow_scratch_rules() {
+    let mut project = new_project();
+    let number = |name: &str| Variable {
+        name: name.to_owned(),
+        value: Constant::Number(0.0),
+    };
+    let mut stage = project.stage();
+    assert!(matches!(
+        stage.add_cloud_variable(Variable {
+            name: "name".to_owned(),
+            value: Constant::String("Alice".into()),
+        }),
+        Err(CloudVariableError::NotNumber)
+    ));
+    for i in 0..10 {
+        let _: sb3_builder::VariableRef = stage
+            .add_cloud_variable(number(&format!("☁ slot {i}")))
+            .unwrap_or_else(|err| panic!("failed to add cloud variable: {err}"));
+    }
+    assert!(stage.find_variable("☁ slot 0").is_some());
+    assert!(matches!(
+        stage.add_cloud_variable(number("slot 10")),
+        Err(CloudVariableError::TooMany)
+    ));
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    assert!(matches!(
+        sprite.add_cloud_variable(number("local")),
+        Err(CloudVariableError::NotStage)
+    ));
+}
+
+#[test]
+fn variables_of_other_sprites_are_errors() {
+    let build = |streaming, monitor| {
+        let mut project = new_project();
+        project.set_streaming(streaming);
+        let mut sprite = add_sprite(&mut project, "Sprite1");
+        let score = sprite.add_variable(Variable {
+            name: "score".to_owned(),
+            value: Constant::Number(0.0),
+        });
+        let mut other = add_sprite(&mut project, "Sprite2");
+        if monitor {
+            other.show_variable_monitor(score, MonitorOptions::default());
+        } else {
+            other.start_script(block::when_flag_clicked());
+            put_all(&mut other, [block::change_variable(score, 1.0.into())]);
+        }
+        // Streams the script above when streaming is enabled.
+        other.start_script(block::when_flag_clicked());
+        project
+    };
+    for (streaming, monitor) in [(false, false), (true, false), (false, true)] {
+        let expected = "invalid target \"Sprite2\": ";
+        let err = build(streaming, monitor)
+            .finish(io::Cursor::new(Vec::new()))
+            .err()
+            .map(|err| err.to_string());
+        assert!(err.is_some_and(|err| err.starts_with(expected)));
+        let err = build(streaming, monitor)
+            .write_json(io::sink())
+            .err()
+            .map(|err| err.to_string());
+        assert!(err.is_some_and(|err| err.starts_with(expected)));
+        let project = build(streaming, monitor);
+        assert!(project.stats().is_err());
+        assert!(project.to_scratchblocks().is_err());
+        assert!(project
+            .export_sprite(
+                "Sprite2",
+                io::Cursor::new(Vec::new()),
+                &FinishOptions::default()
+            )
+            .is_err());
+    }
+}
+
+#[test]
+#[should_panic(expected = "operand was created before its script was streamed")]
+fn operands_of_streamed_scripts_cannot_be_used() {
+    let mut project = new_project();
+    project.set_streaming(true);
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    sprite.start_script(block::when_flag_clicked());
+    let x = sprite.x_position();
+    let sum = sprite.add(x, 1.0.into());
+    sprite.start_script(block::when_flag_clicked());
+    put_all(&mut sprite, [block::say(sum)]);
+}
+
+#[test]
+#[should_panic(expected = "block was created before its script was streamed")]
+fn blocks_of_streamed_scripts_cannot_be_edited() {
+    let mut project = new_project();
+    project.set_streaming(true);
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    sprite.start_script(block::when_flag_clicked());
+    let block = sprite.put(block::say("hi".into()));
+    // Handles can be used until the next script is started.
+    assert_eq!(sprite.opcode(block), "looks_say");
+    sprite.start_script(block::when_flag_clicked());
+    sprite.remove(block);
+}
+
+#[test]
+#[should_panic(expected = "cannot move a stack to an insertion point inside of it")]
+fn stacks_cannot_be_moved_into_themselves() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    sprite.start_script(block::when_flag_clicked());
+    let first = sprite.put(block::say("first".into()));
+    put_all(&mut sprite, [block::say("second".into())]);
+    let after_repeat = sprite.repeat(3.0.into());
+    sprite.move_stack(first, &after_repeat);
+}
+
+#[test]
+#[should_panic(expected = "cannot move a stack to an insertion point outside of any script")]
+fn stacks_cannot_be_moved_outside_of_scripts() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    let (_, body) = sprite.add_custom_block("unused".to_owned(), std::iter::empty());
+    let nowhere = sprite.insert_at(body);
+    sprite.start_script(block::when_flag_clicked());
+    let block = sprite.put(block::say("moved".into()));
+    sprite.move_stack(block, &nowhere);
+}
+
+#[test]
+fn removing_the_only_block_of_a_body() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    sprite.start_script(block::when_flag_clicked());
+    let condition = sprite.key_is_pressed("space".into());
+    let after_if = sprite.if_(condition);
+    let only = sprite.put(block::say("inside".into()));
+    go_to(&mut sprite, after_if);
+    put_all(&mut sprite, [block::hide()]);
+    sprite.remove(only);
+
+    project.optimize();
+    let scratchblocks = project
+        .to_scratchblocks()
+        .unwrap_or_else(|err| panic!("failed to render: {err}"));
+    assert!(scratchblocks.ends_with(
+        "\
+when flag clicked
+if <key [space v] pressed?> then
+end
+hide
+"
+    ));
+    check("emptied_body", project);
+}
+
+#[test]
+#[should_panic(expected = "cannot remove or move a reporter")]
+fn reporters_cannot_be_removed() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    sprite.start_script(block::when_flag_clicked());
+    let x = sprite.x_position();
+    let sum = sprite.add(x, 1.0.into());
+    let reporter = sum.block().unwrap_or_else(|| unreachable!());
+    put_all(&mut sprite, [block::say(sum)]);
+    sprite.remove(reporter);
+}
+
+#[test]
+fn edits() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+
+    sprite.start_script(block::when_flag_clicked());
+    let first = sprite.put(block::say("first".into()));
+    let second = sprite.put(block::say("second".into()));
+    let third = sprite.put(block::say("third".into()));
+    let after_repeat = sprite.repeat(3.0.into());
+    go_to(&mut sprite, after_repeat);
+    let wait = sprite.put(block::wait(1.0.into()));
+    assert_eq!(sprite.opcode(wait), "control_wait");
+
+    let x = sprite.x_position();
+    sprite.set_input(first, "MESSAGE", x);
+    sprite.set_input(second, "MESSAGE", "replaced".into());
+    sprite.remove(second);
+
+    // Moves the third block and the repeat after it into a new script.
+    sprite.start_script(block::when_cloned());
+    put_all(&mut sprite, [block::hide()]);
+    let after_repeat = sprite.repeat(2.0.into());
+    sprite.move_stack(third, &after_repeat);
+
+    check("edits", project);
+}
+
+#[test]
+#[should_panic(expected = "the result of a function can't be used as the condition of a loop")]
+fn function_results_cannot_be_loop_conditions() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    let (function, body) = sprite.add_function("done".to_owned(), std::iter::empty());
+    go_to(&mut sprite, body);
+    sprite.return_value(function, "true".into());
+    sprite.start_script(block::when_flag_clicked());
+    let done = sprite.call_function(function, Vec::new());
+    let _: InsertionPoint = sprite.repeat_until(done);
+}
+
+#[test]
+fn optimized() {
+    let mut project = new_project();
+    project.set_constant_folding(true);
+    {
+        let mut sprite = add_sprite(&mut project, "Sprite1");
+        let v = sprite.add_variable(Variable {
+            name: "v".to_owned(),
+            value: Constant::Number(0.0),
+        });
+
+        sprite.start_script(block::when_flag_clicked());
+        let folded = sprite.add(1.0.into(), 2.0.into());
+        put_all(&mut sprite, [block::say(folded)]);
+        let x = sprite.x_position();
+        let plus_zero = sprite.add(x, 0.0.into());
+        put_all(&mut sprite, [block::set_variable(v, plus_zero)]);
+        let _unused = sprite.timer();
+        sprite.start_script(block::when_received("never sent"));
+        put_all(&mut sprite, [block::say("unreachable".into())]);
+    }
+
+    project.simplify();
+    project.optimize();
+    check("optimized", project);
+}
+
+/// The golden file is written without the `rayon` feature, so this also
+/// checks that serializing targets in parallel doesn't change the output.
+#[test]
+fn many_targets() {
+    let mut project = new_project();
+    for i in 0..8 {
+        let name = format!("Sprite{i}");
+        let mut sprite = project.add_sprite(name.clone());
+        sprite.add_costume(costume("costume1"));
+        sprite.start_script(block::when_flag_clicked());
+        put_all(&mut sprite, [block::say(name.into())]);
+    }
+
+    check("many_targets", project);
+}
+
+/// Writes a `.sprite3` file like one exported from another project, with its
+/// own broadcast ID and a bitmap costume.
+fn sprite3(name: &str, broadcast_id: &str) -> Vec<u8> {
+    let sprite = serde_json::json!({
+        "isStage": false,
+        "name": name,
+        "variables": { "v1": ["score", 0] },
+        "lists": {},
+        "broadcasts": {},
+        "blocks": {
+            "a": {
+                "opcode": "event_whenbroadcastreceived",
+                "next": "b",
+                "parent": null,
+                "inputs": {},
+                "fields": { "BROADCAST_OPTION": ["go", broadcast_id] },
+                "shadow": false,
+                "topLevel": true,
+                "x": 0,
+                "y": 0
+            },
+            "b": {
+                "opcode": "event_broadcast",
+                "next": null,
+                "parent": "a",
+                "inputs": { "BROADCAST_INPUT": [1, [11, "go", broadcast_id]] },
+                "fields": {},
+                "shadow": false,
+                "topLevel": false
+            }
+        },
+        "comments": {},
+        "currentCostume": 0,
+        "costumes": [{
+            "name": "costume1",
+            "bitmapResolution": 2,
+            "dataFormat": "png",
+            "assetId": "0",
+            "md5ext": "costume1.png",
+            "rotationCenterX": 1,
+            "rotationCenterY": 1
+        }],
+        "sounds": [],
+        "volume": 100,
+        "layerOrder": 1,
+        "visible": true,
+        "x": 0,
+        "y": 0,
+        "size": 100,
+        "direction": 90,
+        "draggable": false,
+        "rotationStyle": "all around"
+    });
+    let mut archive = rawzip::ZipArchiveWriter::new(io::Cursor::new(Vec::new()));
+    for (name, content) in [
+        ("sprite.json", sprite.to_string().into_bytes()),
+        ("costume1.png", b"not really a png".to_vec()),
+    ] {
+        let (mut entry, config) = archive
+            .new_file(name)
+            .start()
+            .unwrap_or_else(|err| panic!("failed to add {name}: {err}"));
+        let mut file = config.wrap(&mut entry);
+        io::Write::write_all(&mut file, &content)
+            .unwrap_or_else(|err| panic!("failed to write {name}: {err}"));
+        let (_, descriptor) = file
+            .finish()
+            .unwrap_or_else(|err| panic!("failed to finish {name}: {err}"));
+        let _: u64 = entry
+            .finish(descriptor)
+            .unwrap_or_else(|err| panic!("failed to finish {name}: {err}"));
+    }
+    archive
+        .finish()
+        .unwrap_or_else(|err| panic!("failed to finish archive: {err}"))
+        .into_inner()
+}
+
+#[test]
+fn imported_sprites() {
+    let mut project = new_project();
+    for (name, broadcast_id) in [("Imported1", "first"), ("Imported2", "second")] {
+        let _: Target = project
+            .import_sprite(sprite3(name, broadcast_id).as_slice())
+            .unwrap_or_else(|err| panic!("failed to import {name}: {err}"));
+    }
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    sprite.start_script(block::when_flag_clicked());
+    put_all(&mut sprite, [block::broadcast_and_wait("go".into())]);
+    check("imported_sprites", project);
+}
+
+#[test]
+fn sprite_names_must_be_unique() {
+    let mut project = new_project();
+    let _: Target = project
+        .import_sprite(sprite3("Imported", "first").as_slice())
+        .unwrap_or_else(|err| panic!("failed to import: {err}"));
+    let Err(err) = project.import_sprite(sprite3("Imported", "second").as_slice()) else {
+        panic!("imported a second sprite with the same name");
+    };
+    assert_eq!(
+        err.to_string(),
+        r#"project already has a sprite named "Imported""#
+    );
+}
+
+#[test]
+#[should_panic(expected = r#"project already has a sprite named "Sprite1""#)]
+fn added_sprite_names_must_be_unique() {
+    let mut project = new_project();
+    let _: Target = project.add_sprite("Sprite1");
+    let _: Target = project.add_sprite("Sprite1");
+}
+
+#[test]
+fn exported_sprites_can_be_imported_into_other_projects() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    let counter = sprite.add_variable(Variable {
+        name: "counter".to_owned(),
+        value: Constant::Number(0.0),
+    });
+    sprite.start_script(block::when_flag_clicked());
+    put_all(
+        &mut sprite,
+        [
+            block::change_variable(counter, 1.0.into()),
+            block::say(counter.into()),
+        ],
+    );
+    let mut sprite3 = io::Cursor::new(Vec::new());
+    project
+        .export_sprite("Sprite1", &mut sprite3, &FinishOptions::default())
+        .unwrap_or_else(|err| panic!("failed to export: {err}"));
+    let sprite3 = sprite3.into_inner();
+
+    let Err(err) = project.import_sprite(sprite3.as_slice()) else {
+        panic!("imported a sprite into the project it was exported from");
+    };
+    assert_eq!(
+        err.to_string(),
+        r#"project already has a sprite named "Sprite1""#
+    );
+
+    let mut other = new_project();
+    let _: Target = other
+        .import_sprite(sprite3.as_slice())
+        .unwrap_or_else(|err| panic!("failed to import: {err}"));
+    check("reimported_sprite", other);
+}
+
+#[test]
+fn finish_writes_an_archive() {
+    let mut project = new_project();
+    let mut sprite = add_sprite(&mut project, "Sprite1");
+    sprite.start_script(block::when_flag_clicked());
+    put_all(&mut sprite, [block::say("Hello!".into())]);
+    let mut archive = io::Cursor::new(Vec::new());
+    project
+        .finish(&mut archive)
+        .unwrap_or_else(|err| panic!("failed to finish: {err}"));
+    assert!(!archive.into_inner().is_empty());
+}
+
+#[test]
+fn finish_checks_the_size_limit() {
+    let build = || {
+        let mut project = new_project();
+        let mut sprite = add_sprite(&mut project, "Sprite1");
+        sprite.start_script(block::when_flag_clicked());
+        put_all(&mut sprite, [block::say("Hello!".into())]);
+        project
+    };
+    let size = build()
+        .stats()
+        .unwrap_or_else(|err| panic!("failed to compute stats: {err}"))
+        .project_json_size;
+    let finish = |limit| {
+        let options = FinishOptions {
+            max_project_json_size: Some(limit),
+            ..FinishOptions::default()
+        };
+        build().finish_with(io::Cursor::new(Vec::new()), &options)
+    };
+    assert!(finish(size).is_ok());
+    let Err(err) = finish(size - 1) else {
+        panic!("project.json is over the limit");
+    };
+    assert!(err.to_string().contains("exceeds the limit"), "{err}");
+}
@@ -0,0 +1,773 @@
+//! Runs small projects with the interpreter and checks their variables and
+//! lists afterwards.
+
+#![cfg(feature = "interp")]
+
+use sb3_builder::{
+    block::{self, DeleteIndex, ListIndex},
+    interp::{Interpreter, Value},
+    BlockRef, Constant, InsertionPoint, List, ListRef, Parameter, ParameterKind, Project, Target,
+    Variable, VariableRef,
+};
+
+fn variable<'strings>(target: &mut Target<'strings, '_>, name: &str) -> VariableRef<'strings> {
+    target.add_variable(Variable {
+        name: name.to_owned(),
+        value: Constant::Number(0.0),
+    })
+}
+
+fn list<'strings>(target: &mut Target<'strings, '_>, name: &str) -> ListRef<'strings> {
+    target.add_list(List {
+        name: name.to_owned(),
+        items: Vec::new(),
+    })
+}
+
+fn put(target: &mut Target<'static, '_>, block: block::Stacking<'static>) {
+    let _: BlockRef = target.put(block);
+}
+
+const fn go_to(target: &mut Target<'static, '_>, point: InsertionPoint<'static>) {
+    let _: InsertionPoint = target.insert_at(point);
+}
+
+fn interpreter<'project>(project: &'project Project<'static>) -> Interpreter<'project, 'static> {
+    let mut interpreter =
+        Interpreter::new(project).unwrap_or_else(|err| panic!("failed to interpret: {err}"));
+    interpreter.green_flag();
+    interpreter
+}
+
+const fn number(n: f64) -> Value<'static> {
+    Value::Number(n)
+}
+
+fn string(s: &'static str) -> Value<'static> {
+    Value::String(s.into())
+}
+
+#[test]
+fn operators_cast_like_scratch() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let sum = variable(&mut sprite, "sum");
+    let joined = variable(&mut sprite, "joined");
+    let compared = variable(&mut sprite, "compared");
+    let letter = variable(&mut sprite, "letter");
+
+    sprite.start_script(block::when_flag_clicked());
+    let lhs = sprite.add("1.5".into(), "abc".into());
+    let rhs = sprite.mul(2.0.into(), "0x10".into());
+    let operand = sprite.add(lhs, rhs);
+    put(&mut sprite, block::set_variable(sum, operand));
+    let operand = sprite.join(1.0.into(), "e3".into());
+    put(&mut sprite, block::set_variable(joined, operand));
+    let numeric = sprite.gt("10".into(), "9".into());
+    let textual = sprite.lt("abc".into(), "ABD".into());
+    let operand = sprite.and(numeric, textual);
+    put(&mut sprite, block::set_variable(compared, operand));
+    let operand = sprite.letter_of("hello".into(), 2.0.into());
+    put(&mut sprite, block::set_variable(letter, operand));
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(1.0));
+    assert_eq!(interpreter.variable("Sprite1", "sum"), Some(&number(33.5)));
+    assert_eq!(
+        interpreter.variable("Sprite1", "joined"),
+        Some(&string("1e3"))
+    );
+    assert_eq!(
+        interpreter.variable("Sprite1", "compared"),
+        Some(&Value::Boolean(true))
+    );
+    assert_eq!(
+        interpreter.variable("Sprite1", "letter"),
+        Some(&string("e"))
+    );
+}
+
+#[test]
+fn lists_follow_scratch_semantics() {
+    let mut project = Project::default();
+    let mut stage = project.stage();
+    let items = list(&mut stage, "items");
+    let found = variable(&mut stage, "found");
+    let contents = variable(&mut stage, "contents");
+    let letters = list(&mut stage, "letters");
+    let joined = variable(&mut stage, "joined");
+
+    stage.start_script(block::when_flag_clicked());
+    for item in ["a", "b", "c", "d"] {
+        put(&mut stage, block::append(items, item.into()));
+    }
+    put(&mut stage, block::delete_of_list(items, DeleteIndex::Last));
+    put(
+        &mut stage,
+        block::insert_at_list(items, "xx".into(), ListIndex::At(1.0.into())),
+    );
+    put(
+        &mut stage,
+        block::replace(items, ListIndex::At(2.5.into()), "y".into()),
+    );
+    // Out of range, so nothing happens.
+    put(
+        &mut stage,
+        block::delete_of_list(items, DeleteIndex::At(10.0.into())),
+    );
+    put(
+        &mut stage,
+        block::insert_at_list(items, "z".into(), ListIndex::At(0.0.into())),
+    );
+    let operand = stage.item_num_of_list(items, "B".into());
+    put(&mut stage, block::set_variable(found, operand));
+    let operand = stage.list_contents(items);
+    put(&mut stage, block::set_variable(contents, operand));
+    for letter in ["h", "i"] {
+        put(&mut stage, block::append(letters, letter.into()));
+    }
+    put(&mut stage, block::set_variable(joined, letters.into()));
+    put(&mut stage, block::delete_of_list(letters, DeleteIndex::All));
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(1.0));
+    assert_eq!(
+        interpreter.list("Stage", "items"),
+        Some(
+            ["xx", "y", "b", "c"]
+                .map(|it| Value::String(it.into()))
+                .as_slice()
+        )
+    );
+    // Items are compared case-insensitively.
+    assert_eq!(interpreter.variable("Stage", "found"), Some(&number(3.0)));
+    assert_eq!(
+        interpreter.variable("Stage", "contents"),
+        Some(&string("xx y b c"))
+    );
+    assert_eq!(interpreter.variable("Stage", "joined"), Some(&string("hi")));
+    assert_eq!(interpreter.list("Stage", "letters"), Some([].as_slice()));
+}
+
+#[test]
+fn control_blocks() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let i = variable(&mut sprite, "i");
+    let total = variable(&mut sprite, "total");
+    let branch = variable(&mut sprite, "branch");
+    let log = list(&mut sprite, "log");
+
+    sprite.start_script(block::when_flag_clicked());
+    let after = sprite.repeat("3.5".into());
+    put(&mut sprite, block::change_variable(total, 1.0.into()));
+    go_to(&mut sprite, after);
+    let after = sprite.for_(i, 4.0.into());
+    put(&mut sprite, block::append(log, i.into()));
+    go_to(&mut sprite, after);
+    let condition = sprite.lt(total.into(), 10.0.into());
+    let after = sprite.while_(condition);
+    put(&mut sprite, block::change_variable(total, 2.0.into()));
+    go_to(&mut sprite, after);
+    let condition = sprite.gt(total.into(), 20.0.into());
+    let after = sprite.repeat_until(condition);
+    put(&mut sprite, block::change_variable(total, 5.0.into()));
+    go_to(&mut sprite, after);
+    let condition = sprite.eq(total.into(), "22".into());
+    let [after, else_] = sprite.if_else(condition);
+    put(&mut sprite, block::set_variable(branch, "then".into()));
+    go_to(&mut sprite, else_);
+    put(&mut sprite, block::set_variable(branch, "else".into()));
+    go_to(&mut sprite, after);
+    sprite.forever();
+    put(&mut sprite, block::change_variable(i, 1.0.into()));
+    let condition = sprite.eq(i.into(), 10.0.into());
+    let _: InsertionPoint = sprite.if_(condition);
+    put(&mut sprite, block::stop_all());
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(10.0));
+    // 4 from rounding 3.5, then 4 + 2 * 3 = 10 and 10 + 5 * 3 = 25.
+    assert_eq!(
+        interpreter.variable("Sprite1", "total"),
+        Some(&number(25.0))
+    );
+    assert_eq!(
+        interpreter.list("Sprite1", "log"),
+        Some([1.0, 2.0, 3.0, 4.0].map(Value::Number).as_slice())
+    );
+    assert_eq!(
+        interpreter.variable("Sprite1", "branch"),
+        Some(&string("else"))
+    );
+    assert_eq!(interpreter.variable("Sprite1", "i"), Some(&number(10.0)));
+}
+
+#[test]
+fn loops_yield_unless_in_warp_mode() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let fast = variable(&mut sprite, "fast");
+    let slow = variable(&mut sprite, "slow");
+
+    let (count, body) = sprite.add_custom_block("count".to_owned(), std::iter::empty());
+    let script = sprite.insert_at(body);
+    let _: InsertionPoint = sprite.repeat(1000.0.into());
+    put(&mut sprite, block::change_variable(fast, 1.0.into()));
+    go_to(&mut sprite, script);
+
+    sprite.start_script(block::when_flag_clicked());
+    sprite.use_custom_block(count, Vec::new());
+    sprite.start_script(block::when_flag_clicked());
+    let _: InsertionPoint = sprite.repeat(1000.0.into());
+    put(&mut sprite, block::change_variable(slow, 1.0.into()));
+
+    let mut interpreter = interpreter(&project);
+    interpreter.step();
+    assert_eq!(
+        interpreter.variable("Sprite1", "fast"),
+        Some(&number(1000.0))
+    );
+    let Some(Value::Number(slow)) = interpreter.variable("Sprite1", "slow") else {
+        panic!("slow is not a number");
+    };
+    assert!(*slow < 1000.0);
+    assert!(interpreter.run(100.0));
+    assert_eq!(
+        interpreter.variable("Sprite1", "slow"),
+        Some(&number(1000.0))
+    );
+}
+
+#[test]
+fn waits_use_the_virtual_clock() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let elapsed = variable(&mut sprite, "elapsed");
+
+    sprite.start_script(block::when_flag_clicked());
+    put(&mut sprite, block::wait(2.0.into()));
+    let timer = sprite.timer();
+    put(&mut sprite, block::set_variable(elapsed, timer));
+    put(
+        &mut sprite,
+        block::say_for_seconds(1.0.into(), "Hello!".into()),
+    );
+
+    let mut interpreter = interpreter(&project);
+    assert!(!interpreter.run(2.5));
+    assert_eq!(interpreter.speech("Sprite1"), Some(&string("Hello!")));
+    assert!(interpreter.run(10.0));
+    assert_eq!(interpreter.speech("Sprite1"), None);
+    let Some(Value::Number(elapsed)) = interpreter.variable("Sprite1", "elapsed") else {
+        panic!("elapsed is not a number");
+    };
+    assert!((2.0..2.1).contains(elapsed), "{elapsed}");
+    assert!((3.0..3.2).contains(&interpreter.time()));
+}
+
+#[test]
+fn recursive_custom_blocks() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let log = list(&mut sprite, "log");
+
+    let parameters = [
+        Parameter {
+            name: "n".to_owned(),
+            kind: ParameterKind::StringOrNumber,
+        },
+        Parameter {
+            name: "stop early".to_owned(),
+            kind: ParameterKind::Boolean,
+        },
+    ];
+    let (countdown, body) = sprite.add_custom_block("countdown".to_owned(), parameters.into_iter());
+    let script = sprite.insert_at(body);
+    let n = sprite.custom_block_parameter(countdown, 0);
+    put(&mut sprite, block::append(log, n));
+    let stop_early = sprite.custom_block_parameter(countdown, 1);
+    let after = sprite.if_(stop_early);
+    put(&mut sprite, block::stop_this_script());
+    go_to(&mut sprite, after);
+    let n = sprite.custom_block_parameter(countdown, 0);
+    let condition = sprite.gt(n, 1.0.into());
+    let after = sprite.if_(condition);
+    let n = sprite.custom_block_parameter(countdown, 0);
+    let n_minus_one = sprite.sub(n, 1.0.into());
+    let n = sprite.custom_block_parameter(countdown, 0);
+    let is_three = sprite.eq(n, 3.0.into());
+    sprite.use_custom_block(countdown, vec![n_minus_one, is_three]);
+    go_to(&mut sprite, after);
+    let n = sprite.custom_block_parameter(countdown, 0);
+    put(&mut sprite, block::append(log, n));
+    go_to(&mut sprite, script);
+
+    sprite.start_script(block::when_flag_clicked());
+    let no = sprite.eq(0.0.into(), 1.0.into());
+    sprite.use_custom_block(countdown, vec![5.0.into(), no]);
+    put(&mut sprite, block::append(log, "done".into()));
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(1.0));
+    let expected = [
+        Value::Number(5.0),
+        Value::Number(4.0),
+        Value::Number(3.0),
+        Value::Number(2.0),
+        Value::Number(3.0),
+        Value::Number(4.0),
+        Value::Number(5.0),
+        Value::String("done".into()),
+    ];
+    assert_eq!(
+        interpreter.list("Sprite1", "log"),
+        Some(expected.as_slice())
+    );
+}
+
+#[test]
+fn broadcasts_and_clones() {
+    let mut project = Project::default();
+    let mut stage = project.stage();
+    let received = variable(&mut stage, "received");
+    stage.start_script(block::when_received("Cloned"));
+    put(&mut stage, block::wait(0.0.into()));
+    put(&mut stage, block::change_variable(received, 1.0.into()));
+
+    let mut sprite = project.add_sprite("Sprite1");
+    let own = variable(&mut sprite, "own");
+    let after_broadcast = variable(&mut sprite, "after broadcast");
+    sprite.start_script(block::when_flag_clicked());
+    let _: InsertionPoint = sprite.repeat(3.0.into());
+    sprite.clone_self();
+    put(&mut sprite, block::wait(0.1.into()));
+    sprite.start_script(block::when_cloned());
+    put(&mut sprite, block::set_variable(own, 99.0.into()));
+    put(&mut sprite, block::broadcast_and_wait("cloned".into()));
+    put(
+        &mut sprite,
+        block::set_variable(after_broadcast, 1.0.into()),
+    );
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(5.0));
+    assert_eq!(
+        interpreter.variable("Stage", "received"),
+        Some(&number(3.0))
+    );
+    assert_eq!(interpreter.variable("Sprite1", "own"), Some(&number(0.0)));
+    assert_eq!(
+        interpreter.variable("Sprite1", "after broadcast"),
+        Some(&number(0.0))
+    );
+
+    interpreter.broadcast("CLONED");
+    assert!(interpreter.run(1.0));
+    assert_eq!(
+        interpreter.variable("Stage", "received"),
+        Some(&number(4.0))
+    );
+}
+
+#[test]
+fn keys_start_scripts() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let pressed = variable(&mut sprite, "pressed");
+    sprite.start_script(block::when_key_pressed("space"));
+    let key = sprite.key_is_pressed("space".into());
+    put(&mut sprite, block::set_variable(pressed, key));
+
+    let mut interpreter = interpreter(&project);
+    interpreter.press_key("space");
+    assert!(interpreter.run(1.0));
+    assert_eq!(
+        interpreter.variable("Sprite1", "pressed"),
+        Some(&Value::Boolean(true))
+    );
+}
+
+/// Builds scripts with dead code between blocks that change variables and
+/// lists, so that [`Project::optimize`] has to keep what they do.
+fn project_with_dead_code() -> Project<'static> {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let sum = variable(&mut sprite, "sum");
+    let i = variable(&mut sprite, "i");
+    let log = list(&mut sprite, "log");
+    let (_, unused) = sprite.add_custom_block("unused".to_owned(), std::iter::empty());
+    go_to(&mut sprite, unused);
+    put(&mut sprite, block::set_variable(sum, 100.0.into()));
+
+    sprite.start_script(block::when_flag_clicked());
+    put(&mut sprite, block::show());
+    let body = sprite.for_(i, 3.0.into());
+    put(
+        &mut sprite,
+        block::say_for_seconds(0.0.into(), "counting".into()),
+    );
+    put(&mut sprite, block::change_variable(sum, i.into()));
+    go_to(&mut sprite, body);
+    sprite.start_script(block::when_received("never sent"));
+    put(&mut sprite, block::hide());
+    sprite.start_script(block::when_key_pressed("space"));
+    put(&mut sprite, block::append(log, sum.into()));
+    put(&mut sprite, block::stop_this_script());
+    put(&mut sprite, block::append(log, "after stop".into()));
+    put(&mut sprite, block::say("done".into()));
+    project
+}
+
+#[test]
+fn optimize_keeps_behavior() {
+    let mut optimized = project_with_dead_code();
+    optimized.optimize();
+    for project in [project_with_dead_code(), optimized] {
+        let mut interpreter = interpreter(&project);
+        assert!(interpreter.run(1.0));
+        interpreter.press_key("space");
+        assert!(interpreter.run(1.0));
+        assert_eq!(interpreter.variable("Sprite1", "sum"), Some(&number(6.0)));
+        assert_eq!(
+            interpreter.list("Sprite1", "log"),
+            Some([number(6.0)].as_slice())
+        );
+        assert_eq!(interpreter.speech("Sprite1"), None);
+    }
+}
+
+#[test]
+fn sprites_share_variables_of_the_stage() {
+    let mut project = Project::default();
+    let mut stage = project.stage();
+    let total = variable(&mut stage, "total");
+    let log = list(&mut stage, "log");
+    for name in ["Sprite1", "Sprite2"] {
+        let mut sprite = project.add_sprite(name);
+        let own = variable(&mut sprite, "total");
+        sprite.start_script(block::when_flag_clicked());
+        put(&mut sprite, block::change_variable(total, 1.0.into()));
+        put(&mut sprite, block::change_variable(own, 1.0.into()));
+        put(&mut sprite, block::append(log, name.into()));
+    }
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(1.0));
+    assert_eq!(interpreter.variable("Stage", "total"), Some(&number(2.0)));
+    assert_eq!(interpreter.variable("Sprite1", "total"), Some(&number(1.0)));
+    assert_eq!(
+        interpreter.list("Stage", "log"),
+        Some(&[string("Sprite1"), string("Sprite2")][..])
+    );
+}
+
+#[test]
+fn variables_of_other_sprites_are_rejected() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let own = variable(&mut sprite, "own");
+    let mut other = project.add_sprite("Sprite2");
+    other.start_script(block::when_flag_clicked());
+    put(&mut other, block::set_variable(own, 1.0.into()));
+
+    assert!(Interpreter::new(&project).is_err());
+}
+
+#[test]
+fn streamed_blocks_cannot_be_interpreted() {
+    let mut project = Project::default();
+    project.set_streaming(true);
+    let mut sprite = project.add_sprite("Sprite1");
+    sprite.start_script(block::when_flag_clicked());
+    sprite.start_script(block::when_flag_clicked());
+    assert!(Interpreter::new(&project).is_err());
+}
+
+#[test]
+fn functions_return_values_recursively() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let result = variable(&mut sprite, "result");
+    let log = list(&mut sprite, "log");
+
+    let parameters = [Parameter {
+        name: "n".to_owned(),
+        kind: ParameterKind::StringOrNumber,
+    }];
+    let (fib, body) = sprite.add_function("fib".to_owned(), parameters.into_iter());
+    let script = sprite.insert_at(body);
+    let n = sprite.custom_block_parameter(fib.custom_block(), 0);
+    let condition = sprite.lt(n, 2.0.into());
+    let after = sprite.if_(condition);
+    let n = sprite.custom_block_parameter(fib.custom_block(), 0);
+    sprite.return_value(fib, n);
+    go_to(&mut sprite, after);
+    let n = sprite.custom_block_parameter(fib.custom_block(), 0);
+    let n_minus_one = sprite.sub(n, 1.0.into());
+    let lhs = sprite.call_function(fib, vec![n_minus_one]);
+    let n = sprite.custom_block_parameter(fib.custom_block(), 0);
+    let n_minus_two = sprite.sub(n, 2.0.into());
+    let rhs = sprite.call_function(fib, vec![n_minus_two]);
+    let sum = sprite.add(lhs, rhs);
+    sprite.return_value(fib, sum);
+    go_to(&mut sprite, script);
+
+    sprite.start_script(block::when_flag_clicked());
+    let fib_10 = sprite.call_function(fib, vec![10.0.into()]);
+    put(&mut sprite, block::set_variable(result, fib_10));
+    let fib_3 = sprite.call_function(fib, vec![3.0.into()]);
+    let condition = sprite.eq(fib_3, 2.0.into());
+    let after = sprite.if_(condition);
+    let fib_4 = sprite.call_function(fib, vec![4.0.into()]);
+    put(&mut sprite, block::append(log, fib_4));
+    go_to(&mut sprite, after);
+    let first = sprite.call_function(fib, vec![5.0.into()]);
+    let second = sprite.call_function(fib, vec![6.0.into()]);
+    let joined = sprite.join(first, second);
+    put(&mut sprite, block::append(log, joined));
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(1.0));
+    assert_eq!(
+        interpreter.variable("Sprite1", "result"),
+        Some(&number(55.0))
+    );
+    assert_eq!(
+        interpreter.list("Sprite1", "log"),
+        Some([number(3.0), string("58")].as_slice())
+    );
+    assert_eq!(
+        interpreter.list("Sprite1", "call stack"),
+        Some([].as_slice())
+    );
+}
+
+#[test]
+fn locals_survive_recursion() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let result = variable(&mut sprite, "result");
+    let log = list(&mut sprite, "log");
+
+    let parameters = [Parameter {
+        name: "n".to_owned(),
+        kind: ParameterKind::StringOrNumber,
+    }];
+    let (countdown, body) = sprite.add_custom_block("countdown".to_owned(), parameters.into_iter());
+    let script = sprite.insert_at(body);
+    let frame = sprite.push_frame(vec![Constant::Number(0.0)]);
+    let copy = frame.local(0);
+    let n = sprite.custom_block_parameter(countdown, 0);
+    let set_copy = sprite.set_local(copy, n);
+    put(&mut sprite, set_copy);
+    let n = sprite.custom_block_parameter(countdown, 0);
+    let condition = sprite.gt(n, 0.0.into());
+    let after = sprite.if_(condition);
+    let n = sprite.custom_block_parameter(countdown, 0);
+    let n_minus_one = sprite.sub(n, 1.0.into());
+    sprite.use_custom_block(countdown, vec![n_minus_one]);
+    go_to(&mut sprite, after);
+    let copy = sprite.local(copy);
+    put(&mut sprite, block::append(log, copy));
+    sprite.pop_frame(&frame);
+    go_to(&mut sprite, script);
+
+    let parameters = [Parameter {
+        name: "n".to_owned(),
+        kind: ParameterKind::StringOrNumber,
+    }];
+    let (sum_to, body) = sprite.add_function("sum to".to_owned(), parameters.into_iter());
+    let script = sprite.insert_at(body);
+    let frame = sprite.push_frame(vec![Constant::Number(0.0)]);
+    let total = frame.local(0);
+    let n = sprite.custom_block_parameter(sum_to.custom_block(), 0);
+    let set_total = sprite.set_local(total, n);
+    put(&mut sprite, set_total);
+    let n = sprite.custom_block_parameter(sum_to.custom_block(), 0);
+    let condition = sprite.gt(n, 0.0.into());
+    let after = sprite.if_(condition);
+    let n = sprite.custom_block_parameter(sum_to.custom_block(), 0);
+    let n_minus_one = sprite.sub(n, 1.0.into());
+    let rest = sprite.call_function(sum_to, vec![n_minus_one]);
+    let current = sprite.local(total);
+    let sum = sprite.add(current, rest);
+    let set_total = sprite.set_local(total, sum);
+    put(&mut sprite, set_total);
+    go_to(&mut sprite, after);
+    let total = sprite.local(total);
+    sprite.return_value(sum_to, total);
+    go_to(&mut sprite, script);
+
+    sprite.start_script(block::when_flag_clicked());
+    sprite.use_custom_block(countdown, vec![3.0.into()]);
+    let frame = sprite.push_frame(vec![Constant::String("unused".into())]);
+    let inner = sprite.call_function(sum_to, vec![2.0.into()]);
+    let outer = sprite.call_function(sum_to, vec![inner]);
+    let other = sprite.call_function(sum_to, vec![1.0.into()]);
+    let sum = sprite.add(outer, other);
+    let unused = sprite.local(frame.local(0));
+    let joined = sprite.join(sum, unused);
+    put(&mut sprite, block::set_variable(result, joined));
+    sprite.pop_frame(&frame);
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(1.0));
+    assert_eq!(
+        interpreter.list("Sprite1", "log"),
+        Some([number(0.0), number(1.0), number(2.0), number(3.0)].as_slice())
+    );
+    assert_eq!(
+        interpreter.variable("Sprite1", "result"),
+        Some(&string("7unused"))
+    );
+    assert_eq!(
+        interpreter.list("Sprite1", "call stack"),
+        Some([].as_slice())
+    );
+}
+
+#[test]
+fn removing_the_last_block_of_a_body_keeps_putting_into_it() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let sum = variable(&mut sprite, "sum");
+
+    sprite.start_script(block::when_flag_clicked());
+    let after = sprite.repeat(3.0.into());
+    let removed = sprite.put(block::change_variable(sum, 1.0.into()));
+    sprite.remove(removed);
+    put(&mut sprite, block::change_variable(sum, 2.0.into()));
+    go_to(&mut sprite, after);
+    put(&mut sprite, block::change_variable(sum, 10.0.into()));
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(1.0));
+    assert_eq!(interpreter.variable("Sprite1", "sum"), Some(&number(16.0)));
+}
+
+#[test]
+fn results_are_removed_before_blocks_that_end_scripts() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let (one, body) = sprite.add_function("one".to_owned(), std::iter::empty());
+    go_to(&mut sprite, body);
+    sprite.return_value(one, 1.0.into());
+
+    sprite.start_script(block::when_flag_clicked());
+    let _unused = sprite.call_function(one, Vec::new());
+    put(&mut sprite, block::stop_this_script());
+    sprite.start_script(block::when_flag_clicked());
+    let _unused = sprite.call_function(one, Vec::new());
+    sprite.forever();
+
+    let mut interpreter = interpreter(&project);
+    assert!(!interpreter.run(1.0));
+    assert_eq!(
+        interpreter.list("Sprite1", "call stack"),
+        Some([].as_slice())
+    );
+}
+
+#[test]
+fn framed_custom_blocks_pop_locals_when_stopped() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let log = list(&mut sprite, "log");
+
+    let parameters = [Parameter {
+        name: "n".to_owned(),
+        kind: ParameterKind::StringOrNumber,
+    }];
+    let (countdown, frame, body) = sprite.add_custom_block_with_locals(
+        "countdown".to_owned(),
+        parameters.into_iter(),
+        vec![Constant::Number(0.0)],
+    );
+    let script = sprite.insert_at(body);
+    let copy = frame.local(0);
+    let n = sprite.custom_block_parameter(countdown.body(), 0);
+    let set_copy = sprite.set_local(copy, n);
+    put(&mut sprite, set_copy);
+    let n = sprite.custom_block_parameter(countdown.body(), 0);
+    let condition = sprite.gt(n, 0.0.into());
+    let after = sprite.if_(condition);
+    let n = sprite.custom_block_parameter(countdown.body(), 0);
+    let n_minus_one = sprite.sub(n, 1.0.into());
+    sprite.use_custom_block(countdown.custom_block(), vec![n_minus_one]);
+    go_to(&mut sprite, after);
+    let copy = sprite.local(copy);
+    put(&mut sprite, block::append(log, copy));
+    // Finishing early still pops the frame.
+    put(&mut sprite, block::stop_this_script());
+    go_to(&mut sprite, script);
+
+    sprite.start_script(block::when_flag_clicked());
+    sprite.use_custom_block(countdown.custom_block(), vec![2.0.into()]);
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(1.0));
+    assert_eq!(
+        interpreter.list("Sprite1", "log"),
+        Some([number(0.0), number(1.0), number(2.0)].as_slice())
+    );
+    assert_eq!(
+        interpreter.list("Sprite1", "call stack"),
+        Some([].as_slice())
+    );
+}
+
+#[test]
+fn locals_can_be_used_inside_blocks_that_use_results() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let log = list(&mut sprite, "log");
+    let (two, body) = sprite.add_function("two".to_owned(), std::iter::empty());
+    go_to(&mut sprite, body);
+    sprite.return_value(two, 2.0.into());
+
+    sprite.start_script(block::when_flag_clicked());
+    let frame = sprite.push_frame(vec![Constant::Number(5.0), Constant::String("text".into())]);
+    let (n, text) = (frame.local(0), frame.local(1));
+    let result = sprite.call_function(two, Vec::new());
+    let condition = sprite.eq(result, 2.0.into());
+    let after = sprite.if_(condition);
+    let value = sprite.local(text);
+    put(&mut sprite, block::append(log, value));
+    let set_n = sprite.set_local(n, 6.0.into());
+    put(&mut sprite, set_n);
+    go_to(&mut sprite, after);
+    let times = sprite.call_function(two, Vec::new());
+    let after = sprite.repeat(times);
+    let value = sprite.local(text);
+    put(&mut sprite, block::append(log, value));
+    let result = sprite.call_function(two, Vec::new());
+    let value = sprite.local(n);
+    let sum = sprite.add(value, result);
+    put(&mut sprite, block::append(log, sum));
+    go_to(&mut sprite, after);
+    let value = sprite.local(text);
+    put(&mut sprite, block::append(log, value));
+    sprite.pop_frame(&frame);
+
+    let mut interpreter = interpreter(&project);
+    assert!(interpreter.run(1.0));
+    assert_eq!(
+        interpreter.list("Sprite1", "log"),
+        Some(
+            [
+                string("text"),
+                string("text"),
+                number(8.0),
+                string("text"),
+                number(8.0),
+                string("text")
+            ]
+            .as_slice()
+        )
+    );
+    assert_eq!(
+        interpreter.list("Sprite1", "call stack"),
+        Some([].as_slice())
+    );
+}
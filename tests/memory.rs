@@ -0,0 +1,47 @@
+//! Building a project and dropping it should give back all of its memory,
+//! which matters for long-running compilers that rebuild projects.
+
+use cap::Cap;
+use sb3_builder::{block, Parameter, ParameterKind, Project};
+use std::{alloc::System, io};
+
+/// Counts the bytes that are currently allocated by this test binary.
+#[global_allocator]
+static ALLOCATOR: Cap<System> = Cap::new(System, usize::MAX);
+
+const CALLS: usize = 10_000;
+
+fn build() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let parameters = (0..4).map(|i| Parameter {
+        name: format!("x{i}"),
+        kind: ParameterKind::StringOrNumber,
+    });
+    let (custom_block, _) = sprite.add_custom_block("f".to_owned(), parameters);
+    sprite.start_script(block::when_flag_clicked());
+    for _ in 0..CALLS {
+        let arguments = (0..4).map(|i| f64::from(i).into()).collect();
+        sprite.use_custom_block(custom_block, arguments);
+    }
+    project
+        .write_json(io::sink())
+        .unwrap_or_else(|_| unreachable!());
+}
+
+#[test]
+fn repeated_builds_do_not_grow_memory() {
+    // The first build may initialize things that are never freed.
+    build();
+    let before = ALLOCATOR.allocated();
+    for _ in 0..5 {
+        build();
+    }
+    let growth = ALLOCATOR.allocated().saturating_sub(before);
+    // Leaking a string for every argument would grow by megabytes, while the
+    // test harness may allocate a little on other threads.
+    assert!(
+        growth < 64 * 1024,
+        "memory grew by {growth} bytes over 5 builds"
+    );
+}
@@ -0,0 +1,162 @@
+//! Checks that [`Project::optimize`] removes dead code while keeping the
+//! inputs and fields of the remaining blocks, which are stored apart from the
+//! blocks themselves.
+
+use sb3_builder::{block, BlockRef, Constant, InsertionPoint, List, Project, Target, Variable};
+use serde_json::Value;
+use std::io::{self, Read};
+
+fn put(target: &mut Target<'static, '_>, block: block::Stacking<'static>) {
+    let _: BlockRef = target.put(block);
+}
+
+/// Builds scripts whose blocks all have inputs or fields, with dead code
+/// between them.
+fn project_with_dead_code() -> Project<'static> {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let sum = sprite.add_variable(Variable {
+        name: "sum".to_owned(),
+        value: Constant::Number(0.0),
+    });
+    let i = sprite.add_variable(Variable {
+        name: "i".to_owned(),
+        value: Constant::Number(0.0),
+    });
+    let log = sprite.add_list(List {
+        name: "log".to_owned(),
+        items: Vec::new(),
+    });
+    let (_, unused) = sprite.add_custom_block("unused".to_owned(), std::iter::empty());
+    let _: InsertionPoint = sprite.insert_at(unused);
+    put(&mut sprite, block::set_variable(sum, 100.0.into()));
+
+    sprite.start_script(block::when_flag_clicked());
+    put(&mut sprite, block::show());
+    let body = sprite.for_(i, 3.0.into());
+    put(
+        &mut sprite,
+        block::say_for_seconds(0.0.into(), "counting".into()),
+    );
+    put(&mut sprite, block::change_variable(sum, i.into()));
+    let _: InsertionPoint = sprite.insert_at(body);
+    sprite.start_script(block::when_received("never sent"));
+    put(&mut sprite, block::hide());
+    sprite.start_script(block::when_key_pressed("space"));
+    put(&mut sprite, block::append(log, sum.into()));
+    put(&mut sprite, block::stop_this_script());
+    put(&mut sprite, block::append(log, "after stop".into()));
+    put(&mut sprite, block::say("done".into()));
+    project
+}
+
+/// Returns the blocks of the sprite in the `project.json` file that
+/// [`Project::finish`] writes.
+fn sprite_blocks(project: Project) -> Vec<Value> {
+    let mut archive = io::Cursor::new(Vec::new());
+    project
+        .finish(&mut archive)
+        .unwrap_or_else(|err| panic!("failed to finish: {err}"));
+    let archive = archive.into_inner();
+    let archive = rawzip::ZipArchive::from_slice(&archive)
+        .unwrap_or_else(|err| panic!("invalid archive: {err}"));
+    let entry = archive
+        .entries()
+        .map(|entry| entry.unwrap_or_else(|err| panic!("invalid entry: {err}")))
+        .find(|entry| entry.file_path().as_ref() == b"project.json")
+        .unwrap_or_else(|| panic!("archive has no project.json"));
+    let data = archive
+        .get_entry(entry.wayfinder())
+        .unwrap_or_else(|err| panic!("invalid entry: {err}"))
+        .data();
+    let mut json = String::new();
+    let _: usize = flate2::read::DeflateDecoder::new(data)
+        .read_to_string(&mut json)
+        .unwrap_or_else(|err| panic!("failed to inflate project.json: {err}"));
+    let mut project = serde_json::from_str::<Value>(&json)
+        .unwrap_or_else(|err| panic!("project.json is not valid JSON: {err}"));
+    let Value::Object(blocks) = project["targets"][1]["blocks"].take() else {
+        panic!("sprite has no blocks");
+    };
+    blocks.into_iter().map(|(_, block)| block).collect()
+}
+
+fn opcodes(blocks: &[Value]) -> Vec<&str> {
+    let mut opcodes = blocks
+        .iter()
+        .filter_map(|block| block["opcode"].as_str())
+        .collect::<Vec<_>>();
+    opcodes.sort_unstable();
+    opcodes
+}
+
+#[test]
+fn optimize_removes_dead_code() {
+    let mut project = project_with_dead_code();
+    project.optimize();
+    assert_eq!(
+        opcodes(&sprite_blocks(project)),
+        [
+            "control_for_each",
+            "control_stop",
+            "data_addtolist",
+            "data_changevariableby",
+            "event_whenflagclicked",
+            "event_whenkeypressed",
+            "looks_sayforsecs",
+            "looks_show",
+        ]
+    );
+}
+
+#[test]
+fn optimize_keeps_inputs_and_fields_in_order() {
+    let mut project = project_with_dead_code();
+    project.optimize();
+    let blocks = sprite_blocks(project);
+    let block = |opcode: &str| {
+        blocks
+            .iter()
+            .find(|block| block["opcode"] == opcode)
+            .unwrap_or_else(|| panic!("no {opcode} block"))
+    };
+    assert_eq!(block("control_for_each")["fields"]["VARIABLE"][0], "i");
+    assert_eq!(block("control_for_each")["inputs"]["VALUE"][1][1], 3.0);
+    assert_eq!(
+        block("looks_sayforsecs")["inputs"]["MESSAGE"][1][1],
+        "counting"
+    );
+    assert_eq!(
+        block("data_changevariableby")["fields"]["VARIABLE"][0],
+        "sum"
+    );
+    assert_eq!(block("data_addtolist")["fields"]["LIST"][0], "log");
+    assert_eq!(
+        block("event_whenkeypressed")["fields"]["KEY_OPTION"][0],
+        "space"
+    );
+}
+
+#[test]
+fn optimize_matches_broadcasts_case_insensitively() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    sprite.start_script(block::when_flag_clicked());
+    put(&mut sprite, block::broadcast_and_wait("Game Over".into()));
+    sprite.start_script(block::when_received("game over"));
+    put(&mut sprite, block::hide());
+    sprite.start_script(block::when_received("GAME OVER"));
+    put(&mut sprite, block::show());
+    project.optimize();
+    assert_eq!(
+        opcodes(&sprite_blocks(project)),
+        [
+            "event_broadcastandwait",
+            "event_whenbroadcastreceived",
+            "event_whenbroadcastreceived",
+            "event_whenflagclicked",
+            "looks_hide",
+            "looks_show",
+        ]
+    );
+}
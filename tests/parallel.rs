@@ -0,0 +1,29 @@
+//! Checks that serializing targets in parallel with the `rayon` feature
+//! doesn't depend on how many threads there are.
+
+#![cfg(feature = "rayon")]
+
+use sb3_builder::{block, BlockRef, Project};
+
+#[test]
+fn output_does_not_depend_on_thread_count() {
+    let names = (0..8).map(|i| format!("Sprite{i}")).collect::<Vec<_>>();
+    let mut project = Project::default();
+    for name in &names {
+        let mut sprite = project.add_sprite(name);
+        sprite.start_script(block::when_flag_clicked());
+        let _: BlockRef = sprite.put(block::say(name.as_str().into()));
+    }
+
+    let serialize = |threads| {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap_or_else(|err| panic!("failed to build thread pool: {err}"));
+        let mut json = Vec::new();
+        pool.install(|| project.write_json(&mut json))
+            .unwrap_or_else(|err| panic!("failed to write project.json: {err}"));
+        json
+    };
+    assert!(serialize(1) == serialize(8));
+}
@@ -0,0 +1,243 @@
+use sb3_builder::{
+    block, BlockRef, Constant, InsertionPoint, List, Parameter, ParameterKind, Project, Variable,
+};
+
+#[test]
+fn renders_every_script() {
+    let mut project = Project::default();
+    let mut stage = project.stage();
+    stage.add_comment("Generated code".to_owned());
+    stage.start_script(block::when_received("go [now]"));
+    let _: BlockRef = stage.put(block::say("Hello!".into()));
+
+    let mut sprite = project.add_sprite("Sprite1");
+    let counter = sprite.add_variable(Variable {
+        name: "counter".to_owned(),
+        value: Constant::Number(0.0),
+    });
+    let log = sprite.add_list(List {
+        name: "log".to_owned(),
+        items: Vec::new(),
+    });
+
+    let parameters = [
+        Parameter {
+            name: "n".to_owned(),
+            kind: ParameterKind::StringOrNumber,
+        },
+        Parameter {
+            name: "loud".to_owned(),
+            kind: ParameterKind::Boolean,
+        },
+    ];
+    let (report, body) = sprite.add_custom_block("report".to_owned(), parameters.into_iter());
+    let script = sprite.insert_at(body);
+    let loud = sprite.custom_block_parameter(report, 1);
+    let [after, else_] = sprite.if_else(loud);
+    let n = sprite.custom_block_parameter(report, 0);
+    let _: BlockRef = sprite.put(block::say(n));
+    let _: InsertionPoint = sprite.insert_at(else_);
+    let n = sprite.custom_block_parameter(report, 0);
+    let _: BlockRef = sprite.put(block::append(log, n));
+    let _: InsertionPoint = sprite.insert_at(after);
+    let _: InsertionPoint = sprite.insert_at(script);
+
+    sprite.start_script(block::when_flag_clicked());
+    let _: BlockRef = sprite.put(block::set_variable(counter, 0.0.into()));
+    let limit = sprite.mul(2.5.into(), "4".into());
+    let condition = sprite.lt(counter.into(), limit);
+    let _: InsertionPoint = sprite.while_(condition);
+    let _: BlockRef = sprite.put(block::change_variable(counter, 1.0.into()));
+    let sqrt = sprite.mathop("sqrt", counter.into());
+    let is_key_pressed = sprite.key_is_pressed("space".into());
+    sprite.use_custom_block(report, vec![sqrt, is_key_pressed]);
+    sprite.clone_self();
+
+    assert_eq!(
+        project
+            .to_scratchblocks()
+            .unwrap_or_else(|err| panic!("failed to render: {err}")),
+        "\
+// Stage
+// Generated code
+
+when I receive [go \\[now\\] v]
+say [Hello!]
+
+// Sprite1
+
+define report (n) <loud>
+if <loud> then
+  say (n)
+else
+  add (n) to [log v]
+end
+
+when flag clicked
+set [counter v] to (0)
+while <(counter) < ((2.5) * [4])> :: control
+  change [counter v] by (1)
+  report ([sqrt v] of (counter)) <key [space v] pressed?>
+  create clone of [myself v]
+end
+"
+    );
+}
+
+const RUNTIME: &str = "\
+define pad (text) (length)
+set [result v] to (text)
+repeat until <not <(length of (result)) < (length)>>
+  set [result v] to (join [\\(] (result))
+end
+
+define push (item) <check>
+if <<check> and <[stack v] contains (item)?>> then
+  stop [this script v]
+end
+add (item) to [stack v]
+insert (item) at (1) of [history v]
+replace item (length of [history v]) of [history v] with [last]
+delete (pick random (1) to (length of [history v])) of [history v]
+
+when flag clicked
+delete all of [stack v]
+show variable [result v]
+hide list [history v]
+pad [7] ((2) + (1))
+for each [i v] in (10) :: control
+  push (item (i) of [stack v]) <(i) > (5)>
+  if <(letter (1) of (result)) = [\\(]> then
+    say (item # of (result) in [stack v]) for (0.5) seconds
+  end
+end
+while <<key [space v] pressed?> or <(timer) > (10)>> :: control
+  change x by ((([sqrt v] of (mouse x)) mod (3)) / (-2))
+  go to [front v] layer
+end
+forever
+  wait (1) seconds
+end
+
+when I receive [reset v]
+if <> then
+  repeat until <<> and <>>
+    wait (1) seconds
+  end
+end
+stop [all v]
+";
+
+#[test]
+fn parses_what_it_renders() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    sprite
+        .add_scratchblocks(RUNTIME)
+        .unwrap_or_else(|err| panic!("failed to parse: {err}"));
+    assert_eq!(
+        project
+            .to_scratchblocks()
+            .unwrap_or_else(|err| panic!("failed to render: {err}")),
+        format!("// Stage\n\n// Sprite1\n\n{RUNTIME}")
+    );
+}
+
+#[test]
+fn failed_lines_leave_only_custom_blocks_that_are_used() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    let err = sprite
+        .add_scratchblocks(
+            "\
+when flag clicked
+greet
+unknown block
+
+define greet
+say [Hello!]
+
+define unused
+",
+        )
+        .err()
+        .map(|err| err.to_string());
+    assert_eq!(
+        err.as_deref(),
+        Some("line 3: unknown block \"unknown block\"")
+    );
+    assert_eq!(
+        project
+            .to_scratchblocks()
+            .unwrap_or_else(|err| panic!("failed to render: {err}")),
+        "// Stage\n\n// Sprite1\n\nwhen flag clicked\ngreet\n\ndefine greet\n"
+    );
+}
+
+#[test]
+fn parses_named_list_indices() {
+    let mut project = Project::default();
+    let mut sprite = project.add_sprite("Sprite1");
+    sprite
+        .add_scratchblocks(
+            "\
+when flag clicked
+delete (all) of [log v]
+delete (last v) of [log v]
+insert [x] at (random) of [log v]
+say (item (last) of [log v])
+",
+        )
+        .unwrap_or_else(|err| panic!("failed to parse: {err}"));
+    assert_eq!(
+        project
+            .stats()
+            .unwrap_or_else(|err| panic!("failed to compute stats: {err}"))
+            .targets[1]
+            .variables,
+        0
+    );
+    assert_eq!(
+        project
+            .to_scratchblocks()
+            .unwrap_or_else(|err| panic!("failed to render: {err}")),
+        "\
+// Stage
+
+// Sprite1
+
+when flag clicked
+delete [all] of [log v]
+delete [last] of [log v]
+insert [x] at [random] of [log v]
+say (item [last] of [log v])
+"
+    );
+}
+
+#[test]
+fn reports_invalid_scripts() {
+    for (source, expected) in [
+        ("say [Hello!]", "line 1: block is not in a script"),
+        (
+            "when flag clicked\n\nmove (10) steps\nfly",
+            "line 4: unknown block \"fly\"",
+        ),
+        (
+            "when flag clicked\nforever\nend\nshow",
+            "line 4: blocks cannot follow a `forever` loop",
+        ),
+        (
+            "when flag clicked\nrepeat (3)",
+            "missing `end` at the end of the text",
+        ),
+        ("when flag clicked\nsay (join [a]", "line 2: missing `)`"),
+    ] {
+        let mut project = Project::default();
+        let mut sprite = project.add_sprite("Sprite1");
+        let err = sprite
+            .add_scratchblocks(source)
+            .map_or_else(|err| err.to_string(), |()| String::new());
+        assert_eq!(err, expected);
+    }
+}